@@ -0,0 +1,19 @@
+#!/usr/bin/env python3
+# A bot for the runner's stdin/stdout protocol that plays a random legal action every turn
+import json
+import random
+import sys
+
+position = None
+for line in sys.stdin:
+    message = json.loads(line)
+    if message == "Quit":
+        break
+    if "Position" in message:
+        position = message["Position"]
+    elif "RequestMove" in message:
+        request_id, _ = message["RequestMove"]
+        action = random.choice(position["legal_actions"])
+        print(json.dumps({"Move": [request_id, action]}), flush=True)
+    elif "Result" in message:
+        print("Game over:", message["Result"], file=sys.stderr)
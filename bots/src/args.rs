@@ -0,0 +1,63 @@
+// Command line parsing shared by the binaries. Flags start with `--`, and some take the argument
+// after them as their value
+
+// The value given for `flag`, which has to follow it and can't be another flag
+pub fn arg_value(flag: &str) -> Result<Option<String>, String> {
+    value_in(&std::env::args().skip(1).collect::<Vec<_>>(), flag)
+}
+
+// Arguments that aren't flags or the values of `value_flags`
+pub fn positional_args(value_flags: &[&str]) -> Vec<String> {
+    positional_in(&std::env::args().skip(1).collect::<Vec<_>>(), value_flags)
+}
+
+fn value_in(args: &[String], flag: &str) -> Result<Option<String>, String> {
+    let Some(index) = args.iter().position(|arg| arg == flag) else {
+        return Ok(None);
+    };
+    match args.get(index + 1) {
+        Some(value) if !value.starts_with("--") => Ok(Some(value.clone())),
+        _ => Err(format!("{} needs a value", flag)),
+    }
+}
+
+fn positional_in(args: &[String], value_flags: &[&str]) -> Vec<String> {
+    let mut positional = Vec::new();
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") {
+            // A flag in place of a value is left for `value_in` to complain about
+            if value_flags.contains(&arg.as_str()) {
+                args.next_if(|value| !value.starts_with("--"));
+            }
+        } else {
+            positional.push(arg.clone());
+        }
+    }
+    positional
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn value_in_rejects_a_missing_value_or_another_flag() {
+        let line = args("easy --games 3 --replays --swap-spawns --name");
+        assert_eq!(value_in(&line, "--games"), Ok(Some("3".to_string())));
+        assert_eq!(value_in(&line, "--move-time"), Ok(None));
+        assert!(value_in(&line, "--replays").is_err());
+        assert!(value_in(&line, "--name").is_err());
+    }
+
+    #[test]
+    fn positional_in_skips_flags_and_their_values() {
+        let flags = ["--games", "--replays"];
+        let line = args("easy --games 3 hard --swap-spawns --replays --unrated medium");
+        assert_eq!(positional_in(&line, &flags), ["easy", "hard", "medium"]);
+    }
+}
@@ -0,0 +1,135 @@
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use neurojam24_bots::{arg_value, play_game, play_online, positional_args, Contestant};
+use neurojam24_core::{MatchPreferences, RoomConfig};
+
+const USAGE: &str = "\
+Usage:
+  runner <bot> <bot> [--games N] [--move-time MS] [--swap-spawns] [--replays DIR]
+  runner <bot> --server URL [--name NAME] [--games N] [--move-time MS] [--unrated]
+         [--opponent easy|medium|hard]
+
+A bot is a built-in difficulty (easy, medium or hard) or a command that speaks the line-based
+JSON protocol on stdin and stdout. Quote commands with arguments, e.g. \"python3 bot.py\"";
+const VALUE_FLAGS: [&str; 6] = [
+    "--games",
+    "--move-time",
+    "--replays",
+    "--server",
+    "--name",
+    "--opponent",
+];
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let bots = positional_args(&VALUE_FLAGS);
+    let games = arg_value("--games")?.map_or(1, |games| games.parse().expect("Invalid game count"));
+    let move_time =
+        arg_value("--move-time")?.map_or(1000, |ms| ms.parse().expect("Invalid move time"));
+    let move_time = Duration::from_millis(move_time);
+    match (arg_value("--server")?, bots.as_slice()) {
+        (Some(url), [bot]) => play_server(bot, &url, games, move_time),
+        (None, [first, second]) => play_local([first, second], games, move_time),
+        _ => Err(USAGE.into()),
+    }
+}
+
+// Plays two bots against each other, alternating seats every game
+fn play_local(
+    specs: [&String; 2],
+    games: usize,
+    move_time: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut contestants = [contestant(specs[0])?, contestant(specs[1])?];
+    let names = contestants.each_ref().map(Contestant::name);
+    let swap_spawns = std::env::args().any(|arg| arg == "--swap-spawns");
+    let replays = arg_value("--replays")?.map(PathBuf::from);
+    if let Some(dir) = &replays {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut records = [[0; 3]; 2]; // Wins, losses and draws for each bot
+    for game in 0..games {
+        // The bots trade seats every game and, if asked to, spawns every two games, so each bot
+        // plays from every seat and spawn
+        let seats_swapped = game % 2 == 1;
+        let spawns_swapped = swap_spawns && game % 4 >= 2;
+        let [first, second] = &mut contestants;
+        let seated = match seats_swapped {
+            true => [second, first],
+            false => [first, second],
+        };
+        let replay = play_game(
+            seated,
+            RoomConfig::default(),
+            &[],
+            spawns_swapped,
+            move_time,
+        );
+        let result = replay.result.expect("Finished games have a result");
+        let seat = |bot: usize| (bot + seats_swapped as usize) % 2;
+        for (bot, record) in records.iter_mut().enumerate() {
+            match result.winner() {
+                Some(winner) if winner as usize == seat(bot) => record[0] += 1,
+                Some(_) => record[1] += 1,
+                None => record[2] += 1,
+            }
+        }
+        println!(
+            "Game {}: {} vs {}: {} in {} turns",
+            game + 1,
+            names[seat(0)],
+            names[seat(1)],
+            result,
+            replay.turns.len()
+        );
+        if let Some(dir) = &replays {
+            let name = dir.join(format!("{}", game + 1));
+            std::fs::write(name.with_extension("json"), replay.ser())?;
+            std::fs::write(name.with_extension("txt"), replay.notation())?;
+        }
+    }
+    for (name, [wins, losses, draws]) in names.iter().zip(records) {
+        println!("{}: {}-{}-{}", name, wins, losses, draws);
+    }
+    Ok(())
+}
+
+// Plays a bot in a seat on a server, against whoever matchmaking finds or one of its bots
+fn play_server(
+    spec: &str,
+    url: &str,
+    games: usize,
+    move_time: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut contestant = contestant(spec)?;
+    let name = arg_value("--name")?.unwrap_or_else(|| "Runner".to_string());
+    let preferences = MatchPreferences {
+        rated: !std::env::args().any(|arg| arg == "--unrated"),
+        bot: arg_value("--opponent")?.map(|bot| bot.parse().expect("Invalid opponent difficulty")),
+        ..Default::default()
+    };
+    let results = play_online(&mut contestant, url, &name, preferences, games, move_time)?;
+    let mut record = [0; 3];
+    for (player_id, result) in results {
+        match result.winner() {
+            Some(winner) if winner == player_id => record[0] += 1,
+            Some(_) => record[1] += 1,
+            None => record[2] += 1,
+        }
+    }
+    println!("{}: {}-{}-{}", name, record[0], record[1], record[2]);
+    Ok(())
+}
+
+fn contestant(spec: &str) -> Result<Contestant, String> {
+    Contestant::new(spec).map_err(|error| format!("Couldn't start {}: {}", spec, error))
+}
@@ -0,0 +1,293 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use neurojam24_bots::{arg_value, play_game, positional_args, Contestant, Ratings, Scenario};
+use neurojam24_core::RoomConfig;
+
+const USAGE: &str = "\
+Usage:
+  tournament <bot> <bot>... [--format round-robin|swiss] [--rounds N] [--scenarios FILE]
+             [--move-time MS] [--replays DIR]
+
+A bot is a built-in difficulty (easy, medium or hard) or a command that speaks the line-based
+JSON protocol on stdin and stdout. Quote commands with arguments, e.g. \"python3 bot.py\"
+
+Every match plays each scenario from both spawn arrangements, with each bot taking both seats.
+Scenarios are read from a JSON list of openings, e.g.
+  [{\"name\": \"Forward\", \"opening\": [\"MD+ MU+\", \"AD+ AU+\"]}]
+and default to the standard start. Round-robin plays every pairing once per round, and Swiss
+pairs bots with similar scores for as many rounds as it takes to find a winner";
+const VALUE_FLAGS: [&str; 5] = [
+    "--format",
+    "--rounds",
+    "--scenarios",
+    "--move-time",
+    "--replays",
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    RoundRobin,
+    Swiss,
+}
+
+// Everything a bot has played so far
+#[derive(Default)]
+struct Standing {
+    record: [u32; 3], // Wins, losses and draws
+    score: f64,       // A point for each win and half for each draw or game of a bye
+    opponents: Vec<usize>,
+    bye: bool,
+}
+
+struct Tournament {
+    contestants: Vec<Contestant>,
+    names: Vec<String>,
+    standings: Vec<Standing>,
+    games: Vec<(usize, usize, f64)>, // The bots in seats 0 and 1, and the score of the first
+    scenarios: Vec<Scenario>,
+    move_time: Duration,
+    replays: PathBuf,
+}
+impl Tournament {
+    // Games in a match: each scenario from both spawns, with each bot in both seats
+    fn match_length(&self) -> usize {
+        self.scenarios.len() * 4
+    }
+
+    fn play_match(&mut self, a: usize, b: usize) -> Result<(), Box<dyn std::error::Error>> {
+        for scenario in 0..self.scenarios.len() {
+            for spawns_swapped in [false, true] {
+                for seats in [[a, b], [b, a]] {
+                    self.play(seats, scenario, spawns_swapped)?;
+                }
+            }
+        }
+        self.standings[a].opponents.push(b);
+        self.standings[b].opponents.push(a);
+        Ok(())
+    }
+
+    fn play(
+        &mut self,
+        seats: [usize; 2],
+        scenario: usize,
+        spawns_swapped: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let scenario = &self.scenarios[scenario];
+        let [first, second] = self
+            .contestants
+            .get_disjoint_mut(seats)
+            .expect("A bot doesn't play itself");
+        let replay = play_game(
+            [first, second],
+            RoomConfig::default(),
+            &scenario.opening,
+            spawns_swapped,
+            self.move_time,
+        );
+        let result = replay.result.expect("Finished games have a result");
+        let score = match result.winner() {
+            Some(0) => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        };
+        self.games.push((seats[0], seats[1], score));
+        for (seat, &bot) in seats.iter().enumerate() {
+            let standing = &mut self.standings[bot];
+            match result.winner() {
+                Some(winner) if winner as usize == seat => standing.record[0] += 1,
+                Some(_) => standing.record[1] += 1,
+                None => standing.record[2] += 1,
+            }
+            standing.score += [score, 1.0 - score][seat];
+        }
+        let description = format!(
+            "{} vs {}, {}{}",
+            self.names[seats[0]],
+            self.names[seats[1]],
+            scenario.name,
+            if spawns_swapped {
+                ", spawns swapped"
+            } else {
+                ""
+            }
+        );
+        println!(
+            "Game {}: {}: {} in {} turns",
+            self.games.len(),
+            description,
+            result,
+            replay.turns.len()
+        );
+        let name = self.replays.join(format!("{}", self.games.len()));
+        std::fs::write(name.with_extension("json"), replay.ser())?;
+        let notation = format!("{{{}}}\n{}", description, replay.notation());
+        std::fs::write(name.with_extension("txt"), notation)?;
+        Ok(())
+    }
+
+    // Bots by score, then by rating among bots with the same score
+    fn ranking(&self) -> Vec<usize> {
+        let ratings = Ratings::fit(self.names.len(), &self.games);
+        let mut ranking: Vec<usize> = (0..self.names.len()).collect();
+        ranking.sort_by(|&a, &b| {
+            let score = self.standings[b].score.total_cmp(&self.standings[a].score);
+            score.then(ratings.ratings[b].total_cmp(&ratings.ratings[a]))
+        });
+        ranking
+    }
+
+    // Pairs bots next to each other in the ranking, skipping over ones they've already played
+    // where possible. With an odd number of bots, the lowest ranked one that hasn't had a bye
+    // sits the round out
+    fn swiss_pairings(&mut self) -> Vec<(usize, usize)> {
+        let mut unpaired = self.ranking();
+        if unpaired.len() % 2 == 1 {
+            let position = unpaired
+                .iter()
+                .rposition(|&bot| !self.standings[bot].bye)
+                .unwrap_or(unpaired.len() - 1);
+            let bot = unpaired.remove(position);
+            println!("{} has a bye", self.names[bot]);
+            self.standings[bot].bye = true;
+            self.standings[bot].score += self.match_length() as f64 / 2.0;
+        }
+        let mut pairings = Vec::new();
+        while !unpaired.is_empty() {
+            let bot = unpaired.remove(0);
+            let opponents = &self.standings[bot].opponents;
+            let position = unpaired
+                .iter()
+                .position(|other| !opponents.contains(other))
+                .unwrap_or(0);
+            pairings.push((bot, unpaired.remove(position)));
+        }
+        pairings
+    }
+
+    fn print_standings(&self) {
+        let ratings = Ratings::fit(self.names.len(), &self.games);
+        let width = self.names.iter().map(String::len).max().unwrap_or(0).max(3);
+        println!();
+        println!(
+            "{:>4}  {:<width$}  {:>5}  {:>5}  {:>5}  {:>5}  {:>6}  {:>6}  {:>5}",
+            "Rank", "Bot", "Games", "Won", "Lost", "Drawn", "Score", "Rating", "±95%"
+        );
+        for (rank, bot) in self.ranking().into_iter().enumerate() {
+            let standing = &self.standings[bot];
+            let [wins, losses, draws] = standing.record;
+            println!(
+                "{:>4}  {:<width$}  {:>5}  {:>5}  {:>5}  {:>5}  {:>6.1}  {:>6.0}  {:>5.0}",
+                rank + 1,
+                self.names[bot],
+                wins + losses + draws,
+                wins,
+                losses,
+                draws,
+                standing.score,
+                ratings.ratings[bot],
+                ratings.intervals[bot]
+            );
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let specs = positional_args(&VALUE_FLAGS);
+    if specs.len() < 2 {
+        return Err(USAGE.into());
+    }
+    let format = match arg_value("--format")?.as_deref() {
+        None | Some("round-robin") => Format::RoundRobin,
+        Some("swiss") => Format::Swiss,
+        Some(_) => return Err(USAGE.into()),
+    };
+    let rounds = match (arg_value("--rounds")?, format) {
+        (Some(rounds), _) => rounds.parse().map_err(|_| "Invalid round count")?,
+        (None, Format::RoundRobin) => 1,
+        // Enough rounds for a single bot to win every one
+        (None, Format::Swiss) => specs.len().next_power_of_two().trailing_zeros() as usize,
+    };
+    let move_time = match arg_value("--move-time")? {
+        Some(ms) => ms.parse().map_err(|_| "Invalid move time")?,
+        None => 1000,
+    };
+    let scenarios = match arg_value("--scenarios")? {
+        Some(path) => Scenario::load(Path::new(&path))?,
+        None => vec![Scenario::standard()],
+    };
+    if scenarios.is_empty() {
+        return Err("No scenarios to play".into());
+    }
+    let replays = arg_value("--replays")?
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            PathBuf::from(format!("replays/tournament-{}", time.as_secs()))
+        });
+    std::fs::create_dir_all(&replays)?;
+    let contestants = specs
+        .iter()
+        .map(|spec| {
+            Contestant::new(spec).map_err(|error| format!("Couldn't start {}: {}", spec, error))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // Bots entered more than once are numbered to tell them apart
+    let bases: Vec<String> = contestants.iter().map(Contestant::name).collect();
+    let names = bases
+        .iter()
+        .enumerate()
+        .map(
+            |(bot, base)| match bases.iter().filter(|&name| name == base).count() {
+                1 => base.clone(),
+                _ => {
+                    let entry = bases[..bot].iter().filter(|&name| name == base).count();
+                    format!("{} {}", base, entry + 1)
+                }
+            },
+        )
+        .collect();
+    let mut tournament = Tournament {
+        names,
+        standings: specs.iter().map(|_| Standing::default()).collect(),
+        contestants,
+        games: Vec::new(),
+        scenarios,
+        move_time: Duration::from_millis(move_time),
+        replays,
+    };
+    for round in 0..rounds {
+        println!("Round {}", round + 1);
+        let pairings = match format {
+            Format::RoundRobin => {
+                let bots = tournament.names.len();
+                (0..bots)
+                    .flat_map(|a| (a + 1..bots).map(move |b| (a, b)))
+                    .collect()
+            }
+            Format::Swiss => tournament.swiss_pairings(),
+        };
+        for (a, b) in pairings {
+            tournament.play_match(a, b)?;
+        }
+    }
+    tournament.print_standings();
+    println!("Replays are in {}", tournament.replays.display());
+    Ok(())
+}
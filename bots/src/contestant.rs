@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use neurojam24_core::{
+    bot_action, Difficulty, EngineMessage, GameResult, GameStatus, PlayerAction, Position,
+    RoomConfig,
+};
+
+use crate::{Engine, EngineError};
+
+// Either of the bots that can play a game: a built-in one, or a separate process
+pub enum Contestant {
+    Builtin(Difficulty),
+    External(Engine),
+}
+impl Contestant {
+    // A built-in difficulty by name, e.g. `hard`, or otherwise a command to run
+    pub fn new(spec: &str) -> std::io::Result<Self> {
+        match spec.parse() {
+            Ok(difficulty) => Ok(Contestant::Builtin(difficulty)),
+            Err(()) => Ok(Contestant::External(Engine::spawn(spec)?)),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Contestant::Builtin(difficulty) => format!("{} bot", difficulty),
+            Contestant::External(engine) => engine.command().to_string(),
+        }
+    }
+
+    pub fn start(&mut self, player_id: u8, config: RoomConfig) -> Result<(), EngineError> {
+        match self {
+            Contestant::Builtin(_) => Ok(()),
+            Contestant::External(engine) => {
+                engine
+                    .restart_if_exited()
+                    .map_err(|_| EngineError::Crashed)?;
+                engine.send(&EngineMessage::Config(player_id, config))
+            }
+        }
+    }
+
+    // A legal action for `player_id`, chosen within `time_limit` by external bots
+    pub fn choose(
+        &mut self,
+        status: &GameStatus,
+        player_id: usize,
+        spawns_swapped: bool,
+        time_limit: Duration,
+    ) -> Result<PlayerAction, EngineError> {
+        let legal = status.legal_actions(player_id);
+        if legal.is_empty() {
+            return Err(EngineError::Stuck);
+        }
+        let action = match self {
+            Contestant::Builtin(difficulty) => {
+                let mut rng = rand::thread_rng();
+                bot_action(status, player_id, *difficulty, &mut rng).ok_or(EngineError::Stuck)?
+            }
+            Contestant::External(engine) => {
+                let position = Position::new(status, player_id, spawns_swapped);
+                engine.request_move(position, time_limit)?
+            }
+        };
+        match legal.contains(&action) {
+            true => Ok(action),
+            false => Err(EngineError::Illegal(action)),
+        }
+    }
+
+    pub fn finish(&mut self, result: GameResult) {
+        if let Contestant::External(engine) = self {
+            // A bot that has crashed is restarted before its next game
+            let _ = engine.send(&EngineMessage::Result(result));
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use neurojam24_core::{EngineMessage, EngineReply, PlayerAction, Position, RequestId};
+
+const QUIT_GRACE: Duration = Duration::from_millis(200); // Before a bot that was told to quit is killed
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineError {
+    Crashed,
+    TimedOut,
+    Invalid(String), // A line that isn't a reply
+    Illegal(PlayerAction),
+    Stuck, // No legal action to take
+}
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::Crashed => write!(f, "crashed"),
+            EngineError::TimedOut => write!(f, "ran out of time"),
+            EngineError::Invalid(line) => write!(f, "sent an invalid reply: {}", line),
+            EngineError::Illegal(action) => write!(f, "chose an illegal action {}", action),
+            EngineError::Stuck => write!(f, "has no legal action"),
+        }
+    }
+}
+
+// A bot running as a separate process, speaking the line-based protocol in `EngineMessage`
+pub struct Engine {
+    command: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>, // From stdout, read on a separate thread so requests can time out
+    next_request: RequestId,
+}
+impl Engine {
+    // Runs `command`, split on whitespace into the program and its arguments
+    pub fn spawn(command: &str) -> std::io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Empty bot command")
+        })?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("Stdin is piped");
+        let stdout = child.stdout.take().expect("Stdout is piped");
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            command: command.to_string(),
+            child,
+            stdin,
+            lines,
+            next_request: 0,
+        })
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn has_exited(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+
+    // Starts the process again if it has exited, e.g. after crashing in an earlier game
+    pub fn restart_if_exited(&mut self) -> std::io::Result<()> {
+        if self.has_exited() {
+            *self = Engine::spawn(&self.command)?;
+        }
+        Ok(())
+    }
+
+    pub fn send(&mut self, message: &EngineMessage) -> Result<(), EngineError> {
+        writeln!(self.stdin, "{}", message.ser())
+            .and_then(|()| self.stdin.flush())
+            .map_err(|_| EngineError::Crashed)
+    }
+
+    // Sends the position and waits for the move answering it, not yet checked for legality
+    pub fn request_move(
+        &mut self,
+        position: Position,
+        time_limit: Duration,
+    ) -> Result<PlayerAction, EngineError> {
+        self.next_request += 1;
+        let request_id = self.next_request;
+        let deadline = Instant::now() + time_limit;
+        self.send(&EngineMessage::Position(Box::new(position)))?;
+        let milliseconds = time_limit.as_millis() as u64;
+        self.send(&EngineMessage::RequestMove(request_id, milliseconds))?;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let line = match self.lines.recv_timeout(timeout) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return Err(EngineError::TimedOut),
+                Err(RecvTimeoutError::Disconnected) => return Err(EngineError::Crashed),
+            };
+            match EngineReply::deser(&line) {
+                Ok(EngineReply::Move(id, action)) if id == request_id => return Ok(action),
+                // A reply that missed the deadline of an earlier request
+                Ok(EngineReply::Move(..)) => {}
+                Err(_) => return Err(EngineError::Invalid(line)),
+            }
+        }
+    }
+}
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send(&EngineMessage::Quit);
+        let asked = Instant::now();
+        while !self.has_exited() && asked.elapsed() < QUIT_GRACE {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
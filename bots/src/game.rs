@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use neurojam24_core::{
+    GameResult, GameStatus, PlayerAction, Replay, ReplayTurn, RoomConfig, MAX_REJECTED_TURNS,
+};
+
+use crate::{Contestant, EngineError};
+
+// Plays a game between two contestants, the first in seat 0, after the turns of `opening`. A
+// contestant that crashes, runs out of time, answers with anything but a legal action or gets
+// stuck forfeits
+pub fn play_game(
+    mut contestants: [&mut Contestant; 2],
+    config: RoomConfig,
+    opening: &[[PlayerAction; 2]],
+    spawns_swapped: bool,
+    move_time: Duration,
+) -> Replay {
+    let mut status = match spawns_swapped {
+        true => GameStatus::swapped(),
+        false => GameStatus::initial(),
+    };
+    let mut replay = Replay::new(config);
+    replay.spawns_swapped = spawns_swapped;
+    for &actions in opening {
+        let pushed = status.push_actions(actions);
+        assert!(
+            matches!(pushed, Ok(None)),
+            "Openings are checked when scenarios are made"
+        );
+        replay.push(ReplayTurn {
+            actions,
+            commitments: [None; 2],
+            timed_out: [false; 2],
+        });
+    }
+    let mut errors = [None, None];
+    for (player_id, contestant) in contestants.iter_mut().enumerate() {
+        errors[player_id] = contestant.start(player_id as u8, config).err();
+    }
+    let mut collisions = 0;
+    let result = loop {
+        if errors.iter().any(Option::is_some) {
+            break forfeit(&contestants, &errors);
+        }
+        let mut actions = [None; 2];
+        for (player_id, contestant) in contestants.iter_mut().enumerate() {
+            match contestant.choose(&status, player_id, spawns_swapped, move_time) {
+                Ok(action) => actions[player_id] = Some(action),
+                Err(error) => errors[player_id] = Some(error),
+            }
+        }
+        let [Some(a), Some(b)] = actions else {
+            continue;
+        };
+        match status.push_actions([a, b]) {
+            Ok(result) => {
+                collisions = 0;
+                replay.push(ReplayTurn {
+                    actions: [a, b],
+                    commitments: [None; 2],
+                    timed_out: [false; 2],
+                });
+                if let Some(result) = result.or_else(|| status.stuck_result()) {
+                    break result;
+                }
+            }
+            Err(_) => {
+                // Both players tried to fill the same tile, so the turn is played again
+                collisions += 1;
+                if collisions >= MAX_REJECTED_TURNS {
+                    break GameResult::Draw;
+                }
+            }
+        }
+    };
+    replay.result = Some(result);
+    for contestant in contestants.iter_mut() {
+        contestant.finish(result);
+    }
+    replay
+}
+
+fn forfeit(contestants: &[&mut Contestant; 2], errors: &[Option<EngineError>; 2]) -> GameResult {
+    for (player_id, error) in errors.iter().enumerate() {
+        if let Some(error) = error {
+            eprintln!("{} {}", contestants[player_id].name(), error);
+        }
+    }
+    match errors {
+        [Some(_), Some(_)] => GameResult::Draw,
+        [Some(EngineError::Stuck), None] => GameResult::Win(1),
+        [None, Some(EngineError::Stuck)] => GameResult::Win(0),
+        [Some(EngineError::TimedOut), None] => GameResult::Timeout(0),
+        [None, Some(EngineError::TimedOut)] => GameResult::Timeout(1),
+        [Some(_), None] => GameResult::Resignation(0),
+        _ => GameResult::Resignation(1),
+    }
+}
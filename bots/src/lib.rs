@@ -0,0 +1,15 @@
+mod args;
+mod contestant;
+mod engine;
+mod game;
+mod online;
+mod rating;
+mod scenario;
+
+pub use args::*;
+pub use contestant::*;
+pub use engine::*;
+pub use game::*;
+pub use online::*;
+pub use rating::*;
+pub use scenario::*;
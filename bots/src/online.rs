@@ -0,0 +1,190 @@
+use std::{
+    error::Error,
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use neurojam24_core::{
+    ClientMessage, Commitment, GameResult, GameStatus, InvalidMove, MatchPreferences, Nonce,
+    PlayerAction, RequestId, RoomConfig, RoomPhase, ServerMessage,
+};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use crate::Contestant;
+
+// Between messages, to stay under the rate the server allows however fast the bot is
+const SEND_INTERVAL: Duration = Duration::from_millis(120);
+
+// A contestant's seat on a server, following the game from the messages the room sends
+struct Seat {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    last_sent: Instant,
+    next_request: RequestId,
+    player_id: u8,
+    config: RoomConfig,
+    status: GameStatus,
+    spawns_swapped: bool,
+    in_progress: bool,
+    pending_reveal: Option<(PlayerAction, Nonce)>, // Committed to, and revealed once asked
+}
+impl Seat {
+    fn send(&mut self, message: ClientMessage) -> Result<(), Box<dyn Error>> {
+        std::thread::sleep(SEND_INTERVAL.saturating_sub(self.last_sent.elapsed()));
+        self.last_sent = Instant::now();
+        Ok(self.socket.send(Message::Text(message.ser().into()))?)
+    }
+
+    // Asks the contestant for an action and submits it, or resigns for it if it fails
+    fn submit(
+        &mut self,
+        contestant: &mut Contestant,
+        move_time: Duration,
+    ) -> Result<(), Box<dyn Error>> {
+        let player_id = self.player_id as usize;
+        let action = contestant.choose(&self.status, player_id, self.spawns_swapped, move_time);
+        let action = match action {
+            Ok(action) => action,
+            Err(error) => {
+                eprintln!("{} {}, resigning", contestant.name(), error);
+                self.in_progress = false;
+                return self.send(ClientMessage::Resign);
+            }
+        };
+        self.next_request += 1;
+        match self.config.commit_reveal {
+            true => {
+                let nonce: Nonce = rand::random();
+                self.pending_reveal = Some((action, nonce));
+                let commitment = Commitment::new(self.player_id, action, nonce);
+                self.send(ClientMessage::Commit(self.next_request, commitment))
+            }
+            false => self.send(ClientMessage::Action(self.next_request, action)),
+        }
+    }
+}
+
+// Plays `games` games in a seat on the server at `url`, queueing with `preferences`, and returns
+// the seat and result of each. A crashed external bot resigns and is restarted for the next game
+pub fn play_online(
+    contestant: &mut Contestant,
+    url: &str,
+    name: &str,
+    preferences: MatchPreferences,
+    games: usize,
+    move_time: Duration,
+) -> Result<Vec<(u8, GameResult)>, Box<dyn Error>> {
+    let (socket, _) = tungstenite::connect(url)?;
+    let mut seat = Seat {
+        socket,
+        last_sent: Instant::now(),
+        next_request: 0,
+        player_id: 0,
+        config: RoomConfig::default(),
+        status: GameStatus::initial(),
+        spawns_swapped: false,
+        in_progress: false,
+        pending_reveal: None,
+    };
+    let find_match = ClientMessage::FindMatch(0, name.to_string(), preferences);
+    seat.send(find_match.clone())?;
+    let mut results = Vec::new();
+    let mut opponent_seen = false;
+    let mut opponent_connected = false;
+    loop {
+        let text = match seat.socket.read()? {
+            Message::Text(text) => text,
+            Message::Close(_) => return Err("The server closed the connection".into()),
+            _ => continue,
+        };
+        let Ok(message) = ServerMessage::deser(text.as_str()) else {
+            continue;
+        };
+        match message {
+            ServerMessage::Assign(_, room_id, player_id, _) => {
+                println!("Joined room {} as player {}", room_id, player_id);
+                seat.player_id = player_id;
+            }
+            ServerMessage::Config(config) => seat.config = config,
+            ServerMessage::Phase(RoomPhase::ReadyCheck) => seat.send(ClientMessage::Ready)?,
+            ServerMessage::Start(spawns_swapped) => {
+                seat.spawns_swapped = spawns_swapped;
+                seat.status = match spawns_swapped {
+                    true => GameStatus::swapped(),
+                    false => GameStatus::initial(),
+                };
+                seat.in_progress = true;
+                match contestant.start(seat.player_id, seat.config) {
+                    Ok(()) => seat.submit(contestant, move_time)?,
+                    Err(error) => {
+                        eprintln!("{} {}, resigning", contestant.name(), error);
+                        seat.in_progress = false;
+                        seat.send(ClientMessage::Resign)?;
+                    }
+                }
+            }
+            ServerMessage::Turn(actions) => {
+                let finished = seat.status.push_actions(actions).ok().flatten().is_some();
+                if seat.in_progress && !finished {
+                    seat.submit(contestant, move_time)?;
+                }
+            }
+            // Collisions and voided turns are played again, while refusals leave what was
+            // submitted in place
+            ServerMessage::InvalidMove(_, reason)
+                if seat.in_progress
+                    && !matches!(
+                        reason,
+                        InvalidMove::NotInProgress
+                            | InvalidMove::Uncommitted
+                            | InvalidMove::AlreadySubmitted
+                    ) =>
+            {
+                seat.submit(contestant, move_time)?;
+            }
+            ServerMessage::RevealRequested => {
+                if let Some((action, nonce)) = seat.pending_reveal.take() {
+                    seat.send(ClientMessage::Reveal(seat.next_request, action, nonce))?;
+                }
+            }
+            ServerMessage::Result(result) => {
+                seat.in_progress = false;
+                contestant.finish(result);
+                results.push((seat.player_id, result));
+                println!("Game {}: {}", results.len(), result);
+                if results.len() >= games {
+                    seat.send(ClientMessage::Leave)?;
+                    let _ = seat.socket.close(None);
+                    let _ = seat.socket.flush();
+                    return Ok(results);
+                }
+                if opponent_seen && !opponent_connected {
+                    println!("The opponent didn't come back, finding another");
+                    opponent_seen = false;
+                    seat.send(ClientMessage::Leave)?;
+                    seat.send(find_match.clone())?;
+                    continue;
+                }
+                seat.send(ClientMessage::OfferRematch)?;
+            }
+            ServerMessage::Connected(connected) => {
+                let opponent = connected[1 - seat.player_id as usize];
+                opponent_connected = opponent;
+                if opponent {
+                    opponent_seen = true;
+                } else if opponent_seen && seat.in_progress {
+                    // The server holds their seat for a while, then ends the game as a forfeit
+                    println!("The opponent dropped, waiting for them to come back");
+                } else if opponent_seen {
+                    println!("The opponent left, finding another");
+                    opponent_seen = false;
+                    seat.in_progress = false;
+                    seat.send(ClientMessage::Leave)?;
+                    seat.send(find_match.clone())?;
+                }
+            }
+            ServerMessage::ProtocolError(_, error) => eprintln!("Protocol error: {}", error),
+            ServerMessage::ServerShutdown => return Err("The server shut down".into()),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use neurojam24_core::INITIAL_RATING;
+
+const SCALE: f64 = std::f64::consts::LN_10 / 400.0; // From Elo points to natural log-odds
+const ITERATIONS: usize = 100;
+const Z_95: f64 = 1.96;
+
+// Ratings fitted to a set of games at once by maximum likelihood under the Elo model, unlike the
+// incremental updates of ranked play, so the order games were played in doesn't matter
+pub struct Ratings {
+    pub ratings: Vec<f64>,
+    pub intervals: Vec<f64>, // Half the width of a 95% confidence interval around each rating
+}
+impl Ratings {
+    // `games` holds the two players of each game and the score of the first, 1, 0.5 or 0. Each
+    // player also draws one virtual game against the initial rating, which keeps ratings finite
+    // for players who won or lost everything
+    pub fn fit(players: usize, games: &[(usize, usize, f64)]) -> Self {
+        let prior = INITIAL_RATING as f64;
+        let mut ratings = vec![prior; players];
+        let expected = |a: f64, b: f64| 1.0 / (1.0 + (SCALE * (b - a)).exp());
+        // Σ(score - expected) and Σ p(1 - p) for each player, with the virtual draw
+        let sums = |ratings: &[f64]| {
+            let mut sums: Vec<(f64, f64)> = ratings
+                .iter()
+                .map(|&rating| {
+                    let p = expected(rating, prior);
+                    (0.5 - p, p * (1.0 - p))
+                })
+                .collect();
+            for &(a, b, score) in games {
+                let p = expected(ratings[a], ratings[b]);
+                sums[a].0 += score - p;
+                sums[b].0 -= score - p;
+                sums[a].1 += p * (1.0 - p);
+                sums[b].1 += p * (1.0 - p);
+            }
+            sums
+        };
+        for _ in 0..ITERATIONS {
+            let steps: Vec<f64> = sums(&ratings)
+                .iter()
+                .map(|(gradient, information)| gradient / (SCALE * information))
+                .collect();
+            for (rating, step) in ratings.iter_mut().zip(&steps) {
+                *rating += step.clamp(-400.0, 400.0);
+            }
+            if steps.iter().all(|step| step.abs() < 0.01) {
+                break;
+            }
+        }
+        let intervals = sums(&ratings)
+            .iter()
+            .map(|(_, information)| Z_95 / (SCALE * information.sqrt()))
+            .collect();
+        Self { ratings, intervals }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // How far the first player's score, with the virtual draw, is from what their fitted rating
+    // expects, in games where they sit first against the second player
+    fn gradient(ratings: &Ratings, games: &[(usize, usize, f64)]) -> f64 {
+        let expected = |a: f64, b: f64| 1.0 / (1.0 + (SCALE * (b - a)).exp());
+        let [a, b] = [ratings.ratings[0], ratings.ratings[1]];
+        let played: f64 = games
+            .iter()
+            .map(|&(_, _, score)| score - expected(a, b))
+            .sum();
+        played + 0.5 - expected(a, INITIAL_RATING as f64)
+    }
+
+    #[test]
+    fn fit_without_games_keeps_the_prior() {
+        let ratings = Ratings::fit(2, &[]);
+        assert_eq!(ratings.ratings, [INITIAL_RATING as f64; 2]);
+        // A single virtual draw, with information 1/4
+        let width = Z_95 / (SCALE * 0.5);
+        assert!(ratings.intervals.iter().all(|i| (i - width).abs() < 1e-9));
+    }
+
+    #[test]
+    fn fit_converges_to_the_maximum_likelihood() {
+        // 3 wins out of 4
+        let games = [(0, 1, 1.0), (0, 1, 1.0), (0, 1, 1.0), (0, 1, 0.0)];
+        let ratings = Ratings::fit(2, &games);
+        let [a, b] = [ratings.ratings[0], ratings.ratings[1]];
+        assert!(a > INITIAL_RATING as f64 && b < INITIAL_RATING as f64);
+        // The prior pulls both players equally
+        assert!((a + b - 2.0 * INITIAL_RATING as f64).abs() < 0.1);
+        assert!(gradient(&ratings, &games).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fit_stays_finite_for_a_perfect_score() {
+        let games = [(0, 1, 1.0); 10];
+        let ratings = Ratings::fit(2, &games);
+        assert!(ratings.ratings.iter().all(|r| r.is_finite()));
+        assert!(gradient(&ratings, &games).abs() < 1e-3);
+    }
+
+    #[test]
+    fn intervals_narrow_with_more_games() {
+        let few = [(0, 1, 1.0), (0, 1, 0.0)];
+        let many: Vec<_> = few.iter().copied().cycle().take(200).collect();
+        let [few, many] = [&few[..], &many].map(|games| Ratings::fit(2, games).intervals[0]);
+        // Information grows about linearly with games at even ratings
+        assert!(many < few / 5.0);
+    }
+}
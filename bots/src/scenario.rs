@@ -0,0 +1,68 @@
+use neurojam24_core::{GameStatus, PlayerAction};
+use serde::Deserialize;
+
+// A position to start games from, reached by playing the turns of an opening
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub name: String,
+    pub opening: Vec<[PlayerAction; 2]>,
+}
+impl Scenario {
+    pub fn standard() -> Self {
+        Self {
+            name: "Standard".to_string(),
+            opening: Vec::new(),
+        }
+    }
+
+    // Reads a JSON list of scenarios, each with a name and an opening of turns in notation, e.g.
+    // `[{"name": "Forward", "opening": ["MD+ MU+", "AD+ AU+"]}]`
+    pub fn load(path: &std::path::Path) -> Result<Vec<Self>, String> {
+        #[derive(Deserialize)]
+        struct ScenarioFile {
+            name: String,
+            opening: Vec<String>,
+        }
+
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+        let files: Vec<ScenarioFile> = serde_json::from_str(&text)
+            .map_err(|error| format!("Couldn't parse {}: {}", path.display(), error))?;
+        files
+            .into_iter()
+            .map(|file| {
+                let opening = file
+                    .opening
+                    .iter()
+                    .map(|turn| parse_turn(turn))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| format!("Scenario {} has an invalid turn", file.name))?;
+                Self::new(file.name, opening)
+            })
+            .collect()
+    }
+
+    // Fails unless the opening can be played from either spawn arrangement without ending the game
+    pub fn new(name: String, opening: Vec<[PlayerAction; 2]>) -> Result<Self, String> {
+        for mut status in [GameStatus::initial(), GameStatus::swapped()] {
+            for (turn, &actions) in opening.iter().enumerate() {
+                let legal = (0..2).all(|p| status.legal_actions(p).contains(&actions[p]));
+                if !legal || !matches!(status.push_actions(actions), Ok(None)) {
+                    return Err(format!(
+                        "Scenario {} can't be played at turn {}",
+                        name,
+                        turn + 1
+                    ));
+                }
+            }
+        }
+        Ok(Self { name, opening })
+    }
+}
+
+// Both players' actions, separated by whitespace, e.g. `MD+ MU+`
+fn parse_turn(turn: &str) -> Option<[PlayerAction; 2]> {
+    let mut actions = turn.split_whitespace().map(str::parse);
+    let turn = [actions.next()?.ok()?, actions.next()?.ok()?];
+    actions.next().is_none().then_some(turn)
+}
@@ -15,7 +15,7 @@ pub fn draw_board(ui: &mut Ui, rect: Rect, display: &DisplayData, t: usize, inpu
                 min + vec2(i as f32 * unit, j as f32 * unit),
                 vec2(unit, unit),
             );
-            let col = if let Some((player_id, active, status)) = tile.player() {
+            let col = if let Some((player_id, active, _status)) = tile.player() {
                 player_col(player_id, active)
             } else {
                 Color32::GRAY
@@ -31,7 +31,7 @@ pub fn draw_board(ui: &mut Ui, rect: Rect, display: &DisplayData, t: usize, inpu
                 let dir = action_dir(action) * arrow_length;
                 ui.painter().arrow(rect.center() + dir, dir, (3., col));
             }
-            for (player_id, action, attack) in tile.incoming() {
+            for (_player_id, action, attack) in tile.incoming() {
                 match attack {
                     true => {
                         let col = Color32::ORANGE;
@@ -53,11 +53,11 @@ pub fn draw_board(ui: &mut Ui, rect: Rect, display: &DisplayData, t: usize, inpu
                     }
                 }
             }
-            if let Some((player_id, active, status)) = tile.player() {
+            if let Some((_player_id, _active, status)) = tile.player() {
                 draw_status(ui, rect, status);
             }
             if let Some(input) = input {
-                if let Some((player_id, active, status)) = tile.player() {
+                if let Some((player_id, active, _status)) = tile.player() {
                     if active && player_id == input.player_id {
                         draw_input(ui, rect, &input);
                         // draw_status(ui, rect, status);
@@ -68,7 +68,7 @@ pub fn draw_board(ui: &mut Ui, rect: Rect, display: &DisplayData, t: usize, inpu
     }
 }
 
-fn player_col(player_id: u8, active: bool) -> Color32 {
+pub fn player_col(player_id: u8, active: bool) -> Color32 {
     match player_id {
         0 => match active {
             true => Color32::LIGHT_GREEN,
@@ -1,18 +1,23 @@
 use std::{
+    io::ErrorKind,
     net::TcpStream,
     sync::{Arc, Mutex},
     thread,
 };
 
-use draw::draw_board;
+use draw::{draw_board, player_col};
 use eframe::egui::{self};
 use neurojam24_core::{
-    ActionType, Direction, DisplayData, GameResult, NetBlob, PlayerAction, PlayerStatus,
-    SpatialDirection, TemporalDirection, LENGTH, SIZE,
+    ActionType, ChatMessage, ClientMessage, ClockStatus, Commitment, Difficulty, Direction,
+    DisplayData, Emote, GameResult, InvalidMove, MatchPreferences, MatchScore, Nonce, PlayerAction,
+    PlayerStatus, Profile, ProtocolError, RejoinToken, RequestId, RoomConfig, RoomId, RoomPhase,
+    ServerMessage, SpatialDirection, TemporalDirection, CHAT_BURST, CHAT_RATE, HEARTBEAT_INTERVAL,
+    HEARTBEAT_TIMEOUT, LENGTH, MAX_CHAT_LENGTH,
 };
-use tungstenite::Message;
+use tungstenite::{client_tls_with_config, stream::MaybeTlsStream, Connector, Message};
 
 mod draw;
+mod tls;
 
 /// Native main function
 #[cfg(not(target_arch = "wasm32"))]
@@ -90,6 +95,29 @@ struct Info {
     inputs: [Option<Input>; 2],
     result: Option<GameResult>,
     message: Option<GameMessage>,
+    ready: [bool; 2],
+    config: RoomConfig,
+    deadline: Option<std::time::Instant>,
+    clocks: Option<([ClockStatus; 2], std::time::Instant)>, // Clocks, and when they were received
+    phase: RoomPhase,
+    lobby_ready: [bool; 2],
+    countdown: Option<std::time::Instant>,
+    rematch: [bool; 2],
+    draw_offers: [bool; 2],
+    pause_requests: [bool; 2],
+    score: MatchScore,
+    connection: Connection,
+    connected: [bool; 2], // Which seats the server has a live connection for
+    announcement: Option<String>,
+    room: Option<RoomId>,
+    profiles: [Option<Profile>; 2],
+    queue_position: Option<usize>,     // While looking for a match
+    rated: bool,                       // Whether to look for rated matches
+    bot: Option<Difficulty>,           // Whether to play a server-side bot instead
+    leaderboard: Option<Vec<Profile>>, // Shown until closed
+    chat: Vec<(u8, ChatMessage)>,      // By seat, oldest first
+    muted: bool,                       // Whether to drop the opponent's chat
+    outgoing: Vec<ClientMessage>,      // Requests from the UI for the network thread to send
 }
 impl Info {
     fn new() -> Self {
@@ -99,19 +127,119 @@ impl Info {
             inputs: [None; 2],
             result: None,
             message: None,
+            ready: [false; 2],
+            config: RoomConfig::default(),
+            deadline: None,
+            clocks: None,
+            phase: RoomPhase::Waiting,
+            lobby_ready: [false; 2],
+            countdown: None,
+            rematch: [false; 2],
+            draw_offers: [false; 2],
+            pause_requests: [false; 2],
+            score: MatchScore::default(),
+            connection: Connection::Connecting,
+            connected: [false; 2],
+            announcement: None,
+            room: None,
+            profiles: [None, None],
+            queue_position: None,
+            rated: true,
+            bot: None,
+            leaderboard: None,
+            chat: Vec::new(),
+            muted: false,
+            outgoing: Vec::new(),
         }
     }
+
+    fn preferences(&self) -> MatchPreferences {
+        MatchPreferences {
+            rated: self.rated,
+            bot: self.bot,
+            ..Default::default()
+        }
+    }
+
+    // Names with each player's record, or their seat for players without a profile
+    fn names(&self) -> [String; 2] {
+        std::array::from_fn(|player_id| match &self.profiles[player_id] {
+            Some(profile) => profile.to_string(),
+            None => format!("P{}", player_id),
+        })
+    }
+
+    // Forgets the current room, keeping the connection's state
+    fn leave_room(&mut self) {
+        *self = Info {
+            connection: self.connection.clone(),
+            announcement: self.announcement.take(),
+            rated: self.rated,
+            bot: self.bot,
+            leaderboard: self.leaderboard.take(),
+            muted: self.muted,
+            outgoing: std::mem::take(&mut self.outgoing),
+            ..Info::new()
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Connection {
+    Connecting,
+    Connected,
+    Lost,           // Retrying after a delay
+    Closed(String), // By the server, with its reason
 }
 
 #[derive(Debug, Copy, Clone)]
 enum GameMessage {
-    InvalidMove,
-    MoveConfirmed(),
+    InvalidMove(InvalidMove),
+    MoveConfirmed,
+    ReplayVerified(bool),
+    ProtocolError(ProtocolError),
+}
+
+// `ip.txt` holds a bare host for plain `ws://`, or a `wss://host[:port]` url for TLS
+fn server_url(ip: &str) -> (String, String) {
+    let (scheme, host) = match ip.strip_prefix("wss://") {
+        Some(host) => ("wss", host),
+        None => ("ws", ip.strip_prefix("ws://").unwrap_or(ip)),
+    };
+    let host = host.trim_end_matches('/');
+    let address = match host.contains(':') {
+        true => host.to_string(),
+        false => format!("{}:4444", host),
+    };
+    (format!("{}://{}/", scheme, address), address)
+}
+
+// From `name.txt` next to the executable, or made up if there isn't one
+fn player_name() -> String {
+    std::fs::read_to_string(exe_dir().join("name.txt"))
+        .ok()
+        .and_then(|name| Profile::valid_name(&name).map(str::to_string))
+        .unwrap_or_else(|| format!("Player{}", rand::random::<u16>() % 10000))
+}
+
+fn exe_dir() -> std::path::PathBuf {
+    std::env::current_exe()
+        .ok()
+        .unwrap()
+        .canonicalize()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf()
 }
 
 struct App {
     game_info: Arc<Mutex<Info>>,
     view_slice: usize,
+    chat_open: bool,
+    chat_draft: String,
+    chat_allowance: f32, // Chat messages that can be sent before the server starts dropping them
+    chat_counted: std::time::Instant, // When the allowance was last topped up
 }
 impl App {
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
@@ -119,14 +247,7 @@ impl App {
 
         let info_ref = game_info.clone();
         thread::spawn(move || {
-            let path = std::env::current_exe()
-                .ok()
-                .unwrap()
-                .canonicalize()
-                .unwrap()
-                .parent()
-                .unwrap()
-                .join("ip.txt");
+            let path = exe_dir().join("ip.txt");
             let ip = std::fs::read_to_string(path)
                 .unwrap()
                 .trim()
@@ -134,106 +255,666 @@ impl App {
                 .next()
                 .expect("No ip?")
                 .to_string();
-            let stream = TcpStream::connect((ip, 4444)).expect("Can't connect");
-            let fnuy = tungstenite::client("ws://socket", stream);
-            let (mut socket, _) = fnuy.unwrap();
-            socket.get_mut().set_nonblocking(true);
-
+            let (url, address) = server_url(&ip);
+            let name = player_name();
+            // A certificate next to the executable is the only one trusted, instead of web roots
+            let pinned = std::fs::read(exe_dir().join("pinned.pem"))
+                .ok()
+                .map(|pem| tls::pinned_config(&pem).expect("Invalid pinned certificate"));
             const TICK_LENGTH: std::time::Duration = std::time::Duration::from_millis(5);
-            socket
-                .send(Message::Text(NetBlob::Join.ser().into()))
-                .unwrap();
-            let mut sent_inputs = false;
+            const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+            const MAX_CHAT_LINES: usize = 100; // Older lines are dropped
+                                               // The seat to ask for back after losing the connection, until it's given up
+            let mut held_seat: Option<(RoomId, RejoinToken)> = None;
+            // Commitments this game's turns were played with, to check the replay against
+            let mut own_commitments: Vec<Commitment> = Vec::new();
             loop {
-                let frame_time = std::time::Instant::now();
-                if !sent_inputs {
-                    if let Some(input) =
-                        info_ref.lock().unwrap().inputs[0].and_then(|input| input.evaluate())
-                    {
-                        socket.send(Message::Text(NetBlob::Action(input).ser().into()));
-                        sent_inputs = true;
-                    }
+                info_ref.lock().unwrap().connection = Connection::Connecting;
+                let Some(mut socket) = TcpStream::connect(address.as_str())
+                    .ok()
+                    .and_then(|stream| {
+                        let connector = pinned.clone().map(Connector::Rustls);
+                        client_tls_with_config(url.as_str(), stream, None, connector)
+                            .map_err(|err| println!("Failed to connect: {}", err))
+                            .ok()
+                    })
+                    .map(|(socket, _)| socket)
+                else {
+                    info_ref.lock().unwrap().connection = Connection::Lost;
+                    std::thread::sleep(RECONNECT_DELAY);
+                    continue;
+                };
+                match socket.get_mut() {
+                    MaybeTlsStream::Rustls(stream) => stream.get_mut().set_nonblocking(true),
+                    MaybeTlsStream::Plain(stream) => stream.set_nonblocking(true),
+                    _ => unreachable!("Only rustls is enabled"),
                 }
-                // if sent_inputs {
-                //     if let Some(input) = &mut info_ref.lock().unwrap().inputs[0] {
-                //         input.clear();
-                //     };
-                // }
-                while let Ok(msg) = socket.read() {
-                    if msg.is_text() {
-                        match NetBlob::deser(msg.into_text().expect("It should be").as_str()) {
-                            Ok(blob) => match blob {
-                                NetBlob::Join => todo!(),
-                                NetBlob::Assign(id) => {
-                                    println!("Joined as player {}", id);
-                                    info_ref.lock().unwrap().inputs[0] = Some(Input::new(id));
-                                }
-                                NetBlob::Action(_) => todo!(),
-                                NetBlob::Leave => todo!(),
-                                NetBlob::Display(data) => {
-                                    let mut info = info_ref.lock().unwrap();
-                                    info.display = Some(data);
-                                    if let Some(input) = &mut info.inputs[0] {
-                                        input.clear();
-                                        sent_inputs = false;
-                                    };
-                                    info.message = None;
-                                }
-                                NetBlob::Stati(stati) => {
-                                    info_ref.lock().unwrap().player_stati = Some(stati);
-                                }
-                                NetBlob::Result(result) => {
-                                    info_ref.lock().unwrap().result = Some(result);
-                                }
-                                NetBlob::Start => {}
-                                NetBlob::InvalidMove => {
-                                    let mut info = info_ref.lock().unwrap();
-                                    info.message = Some(GameMessage::InvalidMove);
-                                    if let Some(input) = &mut info.inputs[0] {
-                                        input.clear();
-                                        sent_inputs = false;
-                                    };
-                                }
-                            },
-                            Err(_) => todo!(),
+                .unwrap();
+                {
+                    let mut info = info_ref.lock().unwrap();
+                    info.connection = Connection::Connected;
+                    info.leave_room();
+                }
+
+                let mut next_request: RequestId = 0;
+                // Only a seat lost with the connection is asked for, matches wait for the player.
+                // The server takes it from the old connection if it hasn't noticed it's gone
+                if let Some((room_id, token)) = held_seat {
+                    let rejoin = ClientMessage::Rejoin(next_request, room_id, token);
+                    let _ = socket.send(Message::Text(rejoin.ser().into()));
+                }
+                let mut sent_inputs = false;
+                let mut pending_request: Option<RequestId> = None; // The latest move sent
+                let mut pending_reveal: Option<(PlayerAction, Nonce)> = None;
+                let mut pending_commitment: Option<Commitment> = None; // Until the turn resolves
+                let mut last_seen = std::time::Instant::now();
+                let mut last_ping = std::time::Instant::now();
+                'connection: loop {
+                    let frame_time = std::time::Instant::now();
+                    if !sent_inputs {
+                        let info = info_ref.lock().unwrap();
+                        if let Some((player_id, action)) = info.inputs[0]
+                            .and_then(|input| Some((input.player_id, input.evaluate()?)))
+                        {
+                            next_request += 1;
+                            pending_request = Some(next_request);
+                            let message = if info.config.commit_reveal {
+                                let nonce: Nonce = rand::random();
+                                pending_reveal = Some((action, nonce));
+                                let commitment = Commitment::new(player_id, action, nonce);
+                                pending_commitment = Some(commitment);
+                                ClientMessage::Commit(next_request, commitment)
+                            } else {
+                                ClientMessage::Action(next_request, action)
+                            };
+                            let _ = socket.send(Message::Text(message.ser().into()));
+                            sent_inputs = true;
                         }
                     }
+                    for mut message in info_ref.lock().unwrap().outgoing.drain(..) {
+                        match &mut message {
+                            ClientMessage::FindMatch(request_id, player_name, _) => {
+                                next_request += 1;
+                                *request_id = next_request;
+                                player_name.clone_from(&name);
+                            }
+                            ClientMessage::Leaderboard(request_id) => {
+                                next_request += 1;
+                                *request_id = next_request;
+                            }
+                            ClientMessage::Leave => held_seat = None,
+                            _ => {}
+                        }
+                        let _ = socket.send(Message::Text(message.ser().into()));
+                    }
+                    // if sent_inputs {
+                    //     if let Some(input) = &mut info_ref.lock().unwrap().inputs[0] {
+                    //         input.clear();
+                    //     };
+                    // }
+                    loop {
+                        let msg = match socket.read() {
+                            Ok(msg) => msg,
+                            Err(tungstenite::Error::Io(err))
+                                if err.kind() == ErrorKind::WouldBlock =>
+                            {
+                                break;
+                            }
+                            Err(_) => break 'connection,
+                        };
+                        last_seen = std::time::Instant::now();
+                        if msg.is_text() {
+                            match ServerMessage::deser(
+                                msg.into_text().expect("It should be").as_str(),
+                            ) {
+                                Ok(message) => match message {
+                                    // Seats index per-player state, so anything else is ignored
+                                    ServerMessage::Assign(_, _, id, _)
+                                    | ServerMessage::Chat(id, _)
+                                        if id > 1 =>
+                                    {
+                                        println!("Bad seat from server: {}", id);
+                                    }
+                                    ServerMessage::Assign(_, room_id, id, token) => {
+                                        held_seat = Some((room_id, token));
+                                        let mut info = info_ref.lock().unwrap();
+                                        info.leave_room();
+                                        info.room = Some(room_id);
+                                        info.inputs[0] = Some(Input::new(id));
+                                    }
+                                    ServerMessage::QueuePosition(position) => {
+                                        info_ref.lock().unwrap().queue_position = Some(position);
+                                    }
+                                    ServerMessage::Profiles(profiles) => {
+                                        info_ref.lock().unwrap().profiles = profiles;
+                                    }
+                                    ServerMessage::MatchCancelled => {
+                                        info_ref.lock().unwrap().queue_position = None;
+                                    }
+                                    ServerMessage::Leaderboard(_, leaderboard) => {
+                                        info_ref.lock().unwrap().leaderboard = Some(leaderboard);
+                                    }
+                                    ServerMessage::Chat(player_id, chat) => {
+                                        let mut info = info_ref.lock().unwrap();
+                                        let own = info.inputs[0].map(|input| input.player_id);
+                                        if !info.muted || own == Some(player_id) {
+                                            info.chat.push((player_id, chat));
+                                            let excess =
+                                                info.chat.len().saturating_sub(MAX_CHAT_LINES);
+                                            info.chat.drain(..excess);
+                                        }
+                                    }
+                                    ServerMessage::Display(data) => {
+                                        let mut info = info_ref.lock().unwrap();
+                                        info.display = Some(*data);
+                                        if let Some(input) = &mut info.inputs[0] {
+                                            input.clear();
+                                            sent_inputs = false;
+                                        };
+                                        info.message = None;
+                                        info.ready = [false; 2];
+                                    }
+                                    ServerMessage::Stati(stati) => {
+                                        info_ref.lock().unwrap().player_stati = Some(stati);
+                                    }
+                                    ServerMessage::Result(result) => {
+                                        let mut info = info_ref.lock().unwrap();
+                                        info.result = Some(result);
+                                        info.deadline = None;
+                                    }
+                                    ServerMessage::Start(_) => {
+                                        own_commitments.clear();
+                                        let mut info = info_ref.lock().unwrap();
+                                        info.countdown = None;
+                                        info.message = None;
+                                        info.result = None;
+                                        info.ready = [false; 2];
+                                    }
+                                    ServerMessage::RematchOffers(rematch) => {
+                                        info_ref.lock().unwrap().rematch = rematch;
+                                    }
+                                    ServerMessage::Score(score) => {
+                                        info_ref.lock().unwrap().score = score;
+                                    }
+                                    ServerMessage::Connected(connected) => {
+                                        info_ref.lock().unwrap().connected = connected;
+                                    }
+                                    ServerMessage::DrawOffers(draw_offers) => {
+                                        info_ref.lock().unwrap().draw_offers = draw_offers;
+                                    }
+                                    ServerMessage::PauseRequests(pause_requests) => {
+                                        info_ref.lock().unwrap().pause_requests = pause_requests;
+                                    }
+                                    ServerMessage::Phase(phase) => {
+                                        info_ref.lock().unwrap().phase = phase;
+                                    }
+                                    ServerMessage::LobbyReady(lobby_ready) => {
+                                        info_ref.lock().unwrap().lobby_ready = lobby_ready;
+                                    }
+                                    ServerMessage::Countdown(remaining) => {
+                                        info_ref.lock().unwrap().countdown = Some(
+                                            std::time::Instant::now()
+                                                + std::time::Duration::from_millis(remaining),
+                                        );
+                                    }
+                                    // The board that follows shows the turn already
+                                    ServerMessage::Turn(_) => {
+                                        own_commitments.extend(pending_commitment.take());
+                                    }
+                                    ServerMessage::PlayerReady(player_id) => {
+                                        if let Some(ready) = info_ref
+                                            .lock()
+                                            .unwrap()
+                                            .ready
+                                            .get_mut(player_id as usize)
+                                        {
+                                            *ready = true;
+                                        }
+                                    }
+                                    ServerMessage::Config(config) => {
+                                        info_ref.lock().unwrap().config = config;
+                                    }
+                                    ServerMessage::RevealRequested => {
+                                        if let Some((action, nonce)) = pending_reveal.take() {
+                                            next_request += 1;
+                                            pending_request = Some(next_request);
+                                            let message =
+                                                ClientMessage::Reveal(next_request, action, nonce);
+                                            let _ =
+                                                socket.send(Message::Text(message.ser().into()));
+                                        }
+                                    }
+                                    ServerMessage::Replay(replay) => {
+                                        // The server can't have dropped a commitment of ours
+                                        // and played something else in its place
+                                        let seat = info_ref.lock().unwrap().inputs[0]
+                                            .map(|input| input.player_id as usize);
+                                        let recorded = |commitment: &Commitment| {
+                                            replay.turns.iter().any(|turn| {
+                                                let committed = seat.and_then(|seat| {
+                                                    turn.commitments.get(seat).copied().flatten()
+                                                });
+                                                committed.is_some_and(|(c, _)| c == *commitment)
+                                            })
+                                        };
+                                        let verified =
+                                            replay.verify() && own_commitments.iter().all(recorded);
+                                        let _ = std::fs::write(
+                                            exe_dir().join("replay.json"),
+                                            replay.ser(),
+                                        );
+                                        info_ref.lock().unwrap().message =
+                                            Some(GameMessage::ReplayVerified(verified));
+                                    }
+                                    ServerMessage::TurnTimer(remaining) => {
+                                        info_ref.lock().unwrap().deadline = Some(
+                                            std::time::Instant::now()
+                                                + std::time::Duration::from_millis(remaining),
+                                        );
+                                    }
+                                    ServerMessage::Clocks(clocks) => {
+                                        info_ref.lock().unwrap().clocks =
+                                            Some((clocks, std::time::Instant::now()));
+                                    }
+                                    ServerMessage::ActionAccepted(request_id) => {
+                                        if pending_request == Some(request_id) {
+                                            info_ref.lock().unwrap().message =
+                                                Some(GameMessage::MoveConfirmed);
+                                        }
+                                    }
+                                    ServerMessage::Announcement(text) => {
+                                        info_ref.lock().unwrap().announcement = Some(text);
+                                    }
+                                    ServerMessage::ServerShutdown => {
+                                        // Don't reconnect to a server that is going away
+                                        info_ref.lock().unwrap().connection =
+                                            Connection::Closed("The server shut down".to_string());
+                                        return;
+                                    }
+                                    ServerMessage::ProtocolError(request_id, error) => {
+                                        // The game ended or was forfeited while disconnected
+                                        if request_id == Some(0)
+                                            && error == ProtocolError::NoSeatHeld
+                                        {
+                                            held_seat = None;
+                                        }
+                                        info_ref.lock().unwrap().message =
+                                            Some(GameMessage::ProtocolError(error));
+                                    }
+                                    ServerMessage::InvalidMove(request_id, _)
+                                        if request_id.is_some()
+                                            && request_id != pending_request =>
+                                    {
+                                        // A response to a move that has since been replaced
+                                    }
+                                    ServerMessage::InvalidMove(
+                                        _,
+                                        InvalidMove::AlreadySubmitted,
+                                    ) => {
+                                        // What was submitted before still stands
+                                        pending_request = None;
+                                        pending_commitment = None;
+                                        info_ref.lock().unwrap().message = Some(
+                                            GameMessage::InvalidMove(InvalidMove::AlreadySubmitted),
+                                        );
+                                    }
+                                    ServerMessage::InvalidMove(_, reason) => {
+                                        pending_request = None;
+                                        pending_commitment = None;
+                                        let mut info = info_ref.lock().unwrap();
+                                        info.message = Some(GameMessage::InvalidMove(reason));
+                                        if let Some(input) = &mut info.inputs[0] {
+                                            input.clear();
+                                            sent_inputs = false;
+                                            let player_id = input.player_id as usize;
+                                            info.ready[player_id] = false;
+                                        };
+                                        // The server cleared both players' inputs, so the
+                                        // opponent has to choose again too
+                                        if matches!(
+                                            reason,
+                                            InvalidMove::Collision(_) | InvalidMove::Voided(_)
+                                        ) {
+                                            info.ready = [false; 2];
+                                        }
+                                    }
+                                },
+                                Err(err) => println!("Bad message from server: {}", err),
+                            }
+                        } else if let Message::Close(frame) = msg {
+                            // The server closed the connection on purpose, so don't reconnect
+                            let reason = frame.map(|frame| frame.reason.to_string());
+                            info_ref.lock().unwrap().connection =
+                                Connection::Closed(reason.unwrap_or_default());
+                            return;
+                        }
+                    }
+                    if last_seen.elapsed() >= HEARTBEAT_TIMEOUT {
+                        println!("Connection timed out");
+                        break;
+                    }
+                    if last_ping.elapsed() >= HEARTBEAT_INTERVAL {
+                        last_ping = std::time::Instant::now();
+                        let _ = socket.send(Message::Ping(Default::default()));
+                    }
+                    if frame_time.elapsed() < TICK_LENGTH {
+                        std::thread::sleep(TICK_LENGTH - frame_time.elapsed());
+                    }
                 }
-                if frame_time.elapsed() < TICK_LENGTH {
-                    std::thread::sleep(TICK_LENGTH - frame_time.elapsed());
-                }
+                let mut info = info_ref.lock().unwrap();
+                info.connection = Connection::Lost;
+                info.connected = [false; 2];
+                drop(info);
+                std::thread::sleep(RECONNECT_DELAY);
             }
         });
 
         Self {
             game_info,
             view_slice: 0,
+            chat_open: true,
+            chat_draft: String::new(),
+            chat_allowance: CHAT_BURST,
+            chat_counted: std::time::Instant::now(),
         }
     }
 }
 impl eframe::App for App {
     fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
+        egui::SidePanel::right("chat").show_animated(ctx, self.chat_open, |ui| {
+            let mut info = self.game_info.lock().unwrap();
+            ui.horizontal(|ui| {
+                ui.heading("Chat");
+                ui.checkbox(&mut info.muted, "Mute opponent");
+            });
+            if info.room.is_none() {
+                ui.label("Chat opens once you're in a room");
+                return;
+            }
+            // Matches the server's limit, so nothing sent gets dropped
+            let now = std::time::Instant::now();
+            let regained = now.duration_since(self.chat_counted).as_secs_f32() * CHAT_RATE;
+            self.chat_allowance = (self.chat_allowance + regained).min(CHAT_BURST);
+            self.chat_counted = now;
+            let mut can_send = self.chat_allowance >= 1.0;
+            if !can_send {
+                let wait = (1.0 - self.chat_allowance) / CHAT_RATE;
+                ui.ctx()
+                    .request_repaint_after(std::time::Duration::from_secs_f32(wait));
+            }
+            ui.horizontal_wrapped(|ui| {
+                for emote in Emote::ALL {
+                    let button = ui.add_enabled(can_send, egui::Button::new(emote.to_string()));
+                    if button.on_disabled_hover_text("Slow down").clicked() {
+                        let message = ClientMessage::Chat(ChatMessage::Emote(emote));
+                        info.outgoing.push(message);
+                        self.chat_allowance -= 1.0;
+                        can_send = false;
+                    }
+                }
+            });
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.chat_draft)
+                    .char_limit(MAX_CHAT_LENGTH)
+                    .hint_text("Say something"),
+            );
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                // The draft is kept until it can be sent
+                if can_send && !self.chat_draft.trim().is_empty() {
+                    let text = std::mem::take(&mut self.chat_draft);
+                    info.outgoing
+                        .push(ClientMessage::Chat(ChatMessage::Text(text)));
+                    self.chat_allowance -= 1.0;
+                }
+                response.request_focus();
+            }
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for (player_id, chat) in &info.chat {
+                        let name = match &info.profiles[*player_id as usize] {
+                            Some(profile) => profile.name.clone(),
+                            None => format!("P{}", player_id),
+                        };
+                        let text = egui::RichText::new(format!("{}: {}", name, chat));
+                        ui.label(text.color(player_col(*player_id, true)));
+                    }
+                });
+        });
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.label("View time:");
                 ui.add(egui::Slider::new(&mut self.view_slice, 0..=(LENGTH - 1)));
+                let (ready, clocks, phase, connection, connected, room, queue_position, names) = {
+                    let info = self.game_info.lock().unwrap();
+                    let ready = match info.phase {
+                        RoomPhase::InProgress => info.ready,
+                        _ => info.lobby_ready,
+                    };
+                    (
+                        ready,
+                        info.clocks,
+                        info.phase,
+                        info.connection.clone(),
+                        info.connected,
+                        info.room,
+                        info.queue_position,
+                        info.names(),
+                    )
+                };
+                match &connection {
+                    Connection::Connecting => {
+                        ui.label("Connecting…");
+                        ctx.request_repaint_after(std::time::Duration::from_millis(500));
+                    }
+                    Connection::Lost => {
+                        ui.colored_label(egui::Color32::RED, "Connection lost, reconnecting…");
+                        ctx.request_repaint_after(std::time::Duration::from_millis(500));
+                    }
+                    Connection::Closed(reason) => {
+                        ui.colored_label(egui::Color32::RED, format!("Disconnected: {}", reason));
+                    }
+                    Connection::Connected => {}
+                }
+                if room.is_none() && connection == Connection::Connected {
+                    let mut info = self.game_info.lock().unwrap();
+                    match queue_position {
+                        Some(position) => {
+                            ui.label(format!("Looking for an opponent (#{} in queue)", position));
+                            if ui.button("Cancel").clicked() {
+                                info.outgoing.push(ClientMessage::CancelMatch);
+                            }
+                        }
+                        None => {
+                            let opponent = |bot: Option<Difficulty>| match bot {
+                                Some(difficulty) => format!("{} bot", difficulty),
+                                None => "Players".to_string(),
+                            };
+                            egui::ComboBox::from_id_salt("opponent")
+                                .selected_text(opponent(info.bot))
+                                .show_ui(ui, |ui| {
+                                    let bots = Difficulty::ALL.map(Some);
+                                    for bot in std::iter::once(None).chain(bots) {
+                                        ui.selectable_value(&mut info.bot, bot, opponent(bot));
+                                    }
+                                });
+                            if info.bot.is_none() {
+                                ui.checkbox(&mut info.rated, "Rated");
+                            }
+                            if ui.button("Find match").clicked() {
+                                let preferences = info.preferences();
+                                let message =
+                                    ClientMessage::FindMatch(0, String::new(), preferences);
+                                info.outgoing.push(message);
+                            }
+                        }
+                    }
+                }
+                if let Some(room) = room {
+                    ui.label(format!("Room {}", room));
+                }
+                ui.toggle_value(&mut self.chat_open, "💬 Chat");
+                if connection == Connection::Connected && ui.button("Leaderboard").clicked() {
+                    let mut info = self.game_info.lock().unwrap();
+                    info.outgoing.push(ClientMessage::Leaderboard(0));
+                }
+                for (player_id, ready) in ready.into_iter().enumerate() {
+                    let mut text = match ready {
+                        true => format!("{} ✔", names[player_id]),
+                        false => format!("{} …", names[player_id]),
+                    };
+                    if let Some((clocks, received)) = clocks {
+                        let ClockStatus { remaining, running } = clocks[player_id];
+                        let mut remaining = std::time::Duration::from_millis(remaining);
+                        if running {
+                            remaining = remaining.saturating_sub(received.elapsed());
+                            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+                        }
+                        let secs = remaining.as_secs();
+                        text += &format!(" {}:{:02}", secs / 60, secs % 60);
+                    }
+                    if connection == Connection::Connected && !connected[player_id] {
+                        text += " (offline)";
+                    }
+                    ui.label(egui::RichText::new(text).color(player_col(player_id as u8, true)));
+                }
+                if matches!(phase, RoomPhase::Waiting | RoomPhase::Finished) && room.is_some() {
+                    let mut info = self.game_info.lock().unwrap();
+                    if ui.button("New opponent").clicked() {
+                        info.outgoing.push(ClientMessage::Leave);
+                        let preferences = info.preferences();
+                        info.outgoing
+                            .push(ClientMessage::FindMatch(0, String::new(), preferences));
+                        info.leave_room();
+                    }
+                }
+                match phase {
+                    _ if room.is_none() => {}
+                    RoomPhase::Waiting => {
+                        ui.label("Waiting for opponent");
+                    }
+                    RoomPhase::ReadyCheck | RoomPhase::Countdown => {
+                        let mut info = self.game_info.lock().unwrap();
+                        let own_ready = info.inputs[0]
+                            .map(|input| info.lobby_ready[input.player_id as usize])
+                            .unwrap_or_default();
+                        let text = if own_ready { "Unready" } else { "Ready" };
+                        if ui.button(text).clicked() {
+                            let message = match own_ready {
+                                true => ClientMessage::Unready,
+                                false => ClientMessage::Ready,
+                            };
+                            info.outgoing.push(message);
+                        }
+                        if let Some(countdown) = info.countdown {
+                            let remaining =
+                                countdown.saturating_duration_since(std::time::Instant::now());
+                            ui.label(format!("Starting in {}s", remaining.as_secs_f32().ceil()));
+                            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+                        }
+                    }
+                    RoomPhase::Finished => {
+                        let mut info = self.game_info.lock().unwrap();
+                        if let Some(player_id) = info.inputs[0].map(|input| input.player_id) {
+                            let message = agreement_button(
+                                ui,
+                                player_id,
+                                info.rematch,
+                                ["Offer rematch", "Accept rematch", "Rematch offered"],
+                                [ClientMessage::OfferRematch, ClientMessage::AcceptRematch],
+                            );
+                            info.outgoing.extend(message);
+                        }
+                    }
+                    RoomPhase::InProgress | RoomPhase::Paused => {
+                        let mut info = self.game_info.lock().unwrap();
+                        if let Some(player_id) = info.inputs[0].map(|input| input.player_id) {
+                            if phase == RoomPhase::Paused {
+                                ui.label("Paused");
+                            }
+                            if ui.button("Resign").clicked() {
+                                info.outgoing.push(ClientMessage::Resign);
+                            }
+                            let message = agreement_button(
+                                ui,
+                                player_id,
+                                info.draw_offers,
+                                ["Offer draw", "Accept draw", "Draw offered"],
+                                [ClientMessage::OfferDraw, ClientMessage::AcceptDraw],
+                            );
+                            info.outgoing.extend(message);
+                            let labels = match phase {
+                                RoomPhase::Paused => {
+                                    ["Resume", "Accept resume", "Resume requested"]
+                                }
+                                _ => ["Pause", "Accept pause", "Pause requested"],
+                            };
+                            let request = match phase {
+                                RoomPhase::Paused => ClientMessage::Resume,
+                                _ => ClientMessage::Pause,
+                            };
+                            let message = agreement_button(
+                                ui,
+                                player_id,
+                                info.pause_requests,
+                                labels,
+                                [request.clone(), request],
+                            );
+                            info.outgoing.extend(message);
+                        }
+                    }
+                }
+                let score = self.game_info.lock().unwrap().score;
+                if score.best_of.is_some() || score.wins.iter().sum::<u32>() + score.draws > 0 {
+                    let mut text = format!("Score: {}-{}", score.wins[0], score.wins[1]);
+                    if score.draws > 0 {
+                        text += &format!(" ({} drawn)", score.draws);
+                    }
+                    if let Some(best_of) = score.best_of {
+                        text += &format!(", best of {}", best_of);
+                    }
+                    ui.label(text);
+                }
+                if let Some(deadline) = self.game_info.lock().unwrap().deadline {
+                    let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+                    ui.label(format!("⏱ {}s", remaining.as_secs_f32().ceil()));
+                    ctx.request_repaint_after(std::time::Duration::from_millis(100));
+                }
                 // if let Some(stati) = self.game_info.lock().unwrap().player_stati {
                 //     for status in stati {
                 //         ui.label(status.health.to_string());
                 //     }
                 // }
+                if let Some(announcement) = &self.game_info.lock().unwrap().announcement {
+                    ui.label(format!("📢 {}", announcement));
+                }
                 if let Some(message) = self.game_info.lock().unwrap().message {
                     ui.label(match message {
-                        GameMessage::InvalidMove => {
-                            format!("Invalid move by some player")
+                        GameMessage::InvalidMove(reason) => format!("Invalid move: {}", reason),
+                        GameMessage::MoveConfirmed => "Move confirmed".to_string(),
+                        GameMessage::ProtocolError(error) => format!("Server error: {}", error),
+                        GameMessage::ReplayVerified(true) => "Replay verified".to_string(),
+                        GameMessage::ReplayVerified(false) => {
+                            "Replay failed verification!".to_string()
                         }
-                        GameMessage::MoveConfirmed() => format!("Move confirmed"),
                     });
                 }
                 if let Some(result) = self.game_info.lock().unwrap().result {
+                    let winner = |player_id: u8| match names.get(player_id as usize) {
+                        Some(name) => name.as_str(),
+                        None => "Nobody",
+                    };
                     ui.label(match result {
-                        GameResult::Win(player_id) => format!("Win: {}", player_id),
+                        GameResult::Win(player_id) => format!("{} wins", winner(player_id)),
                         GameResult::Draw => "Draw".to_string(),
+                        GameResult::Timeout(player_id) => {
+                            format!("{} wins (on time)", winner(player_id ^ 1))
+                        }
+                        GameResult::Resignation(player_id) => {
+                            format!("{} wins (resignation)", winner(player_id ^ 1))
+                        }
+                        GameResult::AgreedDraw => "Draw (agreed)".to_string(),
                     });
                 }
             });
@@ -244,6 +925,9 @@ impl eframe::App for App {
                 controls: &[(eframe::egui::Key, T)],
                 target: &mut Option<T>,
             ) {
+                if ui.ctx().wants_keyboard_input() {
+                    return; // Typing in the chat
+                }
                 ui.input(|i| {
                     for &(key, action) in controls {
                         if i.key_pressed(key) {
@@ -295,7 +979,7 @@ impl eframe::App for App {
                 }
             }
 
-            if ui.input(|i| i.key_pressed(egui::Key::Space)) {
+            if !ctx.wants_keyboard_input() && ui.input(|i| i.key_pressed(egui::Key::Space)) {
                 // dbg!(self.game_info.lock().unwrap().inputs[0]);
                 if let Some(input) = &mut self.game_info.lock().unwrap().inputs[0] {
                     input.confirmed = true;
@@ -303,20 +987,62 @@ impl eframe::App for App {
                 if let Some(input) = &mut self.game_info.lock().unwrap().inputs[1] {
                     input.confirmed = true;
                 }
-                self.game_info.lock().unwrap().message = Some(GameMessage::MoveConfirmed());
             }
             // let display = self.game_status.display();
             let guard = self.game_info.lock().unwrap();
             let Info {
-                display,
-                player_stati,
-                inputs,
-                result,
-                message,
+                display, inputs, ..
             } = &*guard;
             if let Some(display) = display {
                 draw_board(ui, rect, display, self.view_slice, inputs[0]);
             }
         });
+
+        let mut info = self.game_info.lock().unwrap();
+        if let Some(leaderboard) = &info.leaderboard {
+            let mut open = true;
+            egui::Window::new("Leaderboard")
+                .open(&mut open)
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    if leaderboard.is_empty() {
+                        ui.label("No rated games yet");
+                    }
+                    egui::Grid::new("leaderboard").striped(true).show(ui, |ui| {
+                        for (index, profile) in leaderboard.iter().enumerate() {
+                            ui.label(format!("{}.", index + 1));
+                            ui.label(&profile.name);
+                            ui.label(profile.rating.to_string());
+                            let record = (profile.wins, profile.losses, profile.draws);
+                            ui.label(format!("{}-{}-{}", record.0, record.1, record.2));
+                            ui.end_row();
+                        }
+                    });
+                });
+            if !open {
+                info.leaderboard = None;
+            }
+        }
+    }
+}
+
+// Button for a request both players have to agree to, with labels for offering, accepting
+// and waiting on the opponent. Returns the message to send when clicked
+fn agreement_button(
+    ui: &mut egui::Ui,
+    player_id: u8,
+    requests: [bool; 2],
+    [offer_label, accept_label, pending_label]: [&str; 3],
+    [offer, accept]: [ClientMessage; 2],
+) -> Option<ClientMessage> {
+    let own = requests[player_id as usize];
+    let other = requests[1 - player_id as usize];
+    if own {
+        ui.label(pending_label);
+        None
+    } else if other {
+        ui.button(accept_label).clicked().then_some(accept)
+    } else {
+        ui.button(offer_label).clicked().then_some(offer)
     }
 }
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 use crate::{Direction, PlayerAction, PlayerStatus, SpatialDirection, TemporalDirection};
 
 #[derive(Debug, Default, Copy, Clone)]
@@ -24,7 +26,7 @@ pub struct TileState {
 }
 impl TileState {
     pub fn is_empty(&self) -> bool {
-        self.player.is_none() && self.hazard == false
+        self.player.is_none() && !self.hazard
     }
     pub fn is_movable(&self) -> bool {
         self.player.is_none()
@@ -73,17 +75,17 @@ impl BoardState {
         }
     }
 
-    pub fn get(&self, x: usize, y: usize) -> Result<TileState, ()> {
+    pub fn get(&self, x: usize, y: usize) -> Result<TileState, InvalidMove> {
         if x < SIZE && y < SIZE {
             return Ok(self.tiles[y][x]);
         }
-        Err(())
+        Err(InvalidMove::OutOfBounds)
     }
-    pub fn get_mut(&mut self, x: usize, y: usize) -> Result<&mut TileState, ()> {
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Result<&mut TileState, InvalidMove> {
         if x < SIZE && y < SIZE {
             return Ok(&mut self.tiles[y][x]);
         }
-        Err(())
+        Err(InvalidMove::OutOfBounds)
     }
     pub fn set(&mut self, x: usize, y: usize, state: TileState) -> bool {
         if x < SIZE && y < SIZE {
@@ -126,26 +128,30 @@ impl Board {
         },
     ];
     pub fn initial() -> Self {
+        Self::with_spawns(Self::SPAWNS)
+    }
+
+    pub fn with_spawns(spawns: [Stamp; 2]) -> Self {
         let mut board = Self {
             states: core::array::from_fn(|_| BoardState::empty()),
         };
-        for i in 0..2 {
-            board.set(Self::SPAWNS[i], TileState::player(i as u8));
+        for (i, spawn) in spawns.into_iter().enumerate() {
+            board.set(spawn, TileState::player(i as u8));
         }
         board
     }
 
-    pub fn get(&self, stamp: Stamp) -> Result<TileState, ()> {
+    pub fn get(&self, stamp: Stamp) -> Result<TileState, InvalidMove> {
         if stamp.t < LENGTH {
             return self.states[stamp.t].get(stamp.x, stamp.y);
         }
-        Err(())
+        Err(InvalidMove::OutOfBounds)
     }
-    fn get_mut(&mut self, stamp: Stamp) -> Result<&mut TileState, ()> {
+    fn get_mut(&mut self, stamp: Stamp) -> Result<&mut TileState, InvalidMove> {
         if stamp.t < LENGTH {
             return self.states[stamp.t].get_mut(stamp.x, stamp.y);
         }
-        Err(())
+        Err(InvalidMove::OutOfBounds)
     }
 
     fn set(&mut self, stamp: Stamp, state: TileState) -> bool {
@@ -189,9 +195,8 @@ impl Board {
         player_id: usize,
         action: PlayerAction,
         source: Stamp,
-    ) -> Result<(Stamp, Impact), Error> {
-        let mut target = source.clone();
-        target = target + action.direction;
+    ) -> Result<(Stamp, Impact), InvalidMove> {
+        let target = source + action.direction;
 
         let mut player = None;
         let mut attack = None;
@@ -201,9 +206,7 @@ impl Board {
                     if state.is_movable() {
                         player = Some((target, player_id as u8));
                     } else {
-                        dbg!(player_id);
-                        dbg!(source);
-                        return Err(Error::InvalidMove("Target is occupied"));
+                        return Err(InvalidMove::Occupied);
                     }
                 }
                 crate::ActionType::Attack => {
@@ -213,7 +216,7 @@ impl Board {
                         .expect("They're already here come on")
                         .is_movable()
                     {
-                        return Err(Error::InvalidMove("Target is occupied (stationary)"));
+                        return Err(InvalidMove::OccupiedStationary);
                     }
                     player = Some((player_dest, player_id as u8));
                     attack = Some(target);
@@ -223,7 +226,7 @@ impl Board {
         if let Some(player) = player {
             Ok((source, Impact { player, attack }))
         } else {
-            Err(Error::InvalidMove("Illegal state"))
+            Err(InvalidMove::OutOfBounds)
         }
     }
 }
@@ -237,7 +240,7 @@ impl std::fmt::Display for Board {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Stamp {
     pub x: usize,
     pub y: usize,
@@ -247,11 +250,11 @@ impl std::ops::Add<SpatialDirection> for Stamp {
     type Output = Self;
 
     fn add(self, rhs: SpatialDirection) -> Self::Output {
-        let mut out = self.clone();
+        let mut out = self;
         match rhs {
-            SpatialDirection::Left => out.x -= 1,
+            SpatialDirection::Left => out.x = out.x.wrapping_sub(1),
             SpatialDirection::Right => out.x += 1,
-            SpatialDirection::Up => out.y -= 1,
+            SpatialDirection::Up => out.y = out.y.wrapping_sub(1),
             SpatialDirection::Down => out.y += 1,
         };
         out
@@ -261,10 +264,10 @@ impl std::ops::Add<TemporalDirection> for Stamp {
     type Output = Self;
 
     fn add(self, rhs: TemporalDirection) -> Self::Output {
-        let mut out = self.clone();
+        let mut out = self;
         match rhs {
             TemporalDirection::Forward => out.t += 1,
-            TemporalDirection::Backward => out.t -= 1,
+            TemporalDirection::Backward => out.t = out.t.wrapping_sub(1),
         };
         out
     }
@@ -279,7 +282,37 @@ impl std::ops::Add<Direction> for Stamp {
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Error {
-    InvalidMove(&'static str),
+    InvalidMove([Option<InvalidMove>; 2]), // Why each player's action was rejected, if it was
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InvalidMove {
+    Occupied,
+    OccupiedStationary,
+    OutOfBounds,
+    Collision(u8), // Both players tried to fill the same tile, along with the other player
+    Uncommitted,
+    CommitmentMismatch,
+    Voided(u8), // The other player's reveal was rejected, so both must choose again
+    NotInProgress,
+    AlreadySubmitted, // Commitments are binding until the turn resolves or is voided
+    CommitRevealOff,  // A commitment sent to a room that takes plain actions
+}
+impl std::fmt::Display for InvalidMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidMove::Occupied => write!(f, "Target is occupied"),
+            InvalidMove::OccupiedStationary => write!(f, "Target is occupied (stationary)"),
+            InvalidMove::OutOfBounds => write!(f, "Target is out of bounds"),
+            InvalidMove::Collision(player_id) => write!(f, "Collided with player {}", player_id),
+            InvalidMove::Uncommitted => write!(f, "Action must be committed first"),
+            InvalidMove::CommitmentMismatch => write!(f, "Reveal does not match commitment"),
+            InvalidMove::Voided(player_id) => write!(f, "Turn voided by player {}", player_id),
+            InvalidMove::NotInProgress => write!(f, "Game is not in progress"),
+            InvalidMove::AlreadySubmitted => write!(f, "An action was already submitted"),
+            InvalidMove::CommitRevealOff => write!(f, "Commit-reveal is off in this room"),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
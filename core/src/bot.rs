@@ -0,0 +1,104 @@
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{GameStatus, PlayerAction};
+
+// How well a built-in opponent plays
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,   // Any legal action
+    Medium, // The best action on average over the opponent's replies
+    Hard,   // The best action against the opponent's strongest reply
+}
+impl Difficulty {
+    pub const ALL: [Self; 3] = [Self::Easy, Self::Medium, Self::Hard];
+}
+impl std::str::FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "hard" => Ok(Self::Hard),
+            _ => Err(()),
+        }
+    }
+}
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "Easy"),
+            Difficulty::Medium => write!(f, "Medium"),
+            Difficulty::Hard => write!(f, "Hard"),
+        }
+    }
+}
+
+// An action for `player_id`, or `None` if it has no legal action. Ties are broken at random
+pub fn bot_action(
+    status: &GameStatus,
+    player_id: usize,
+    difficulty: Difficulty,
+    rng: &mut impl Rng,
+) -> Option<PlayerAction> {
+    let actions = status.legal_actions(player_id);
+    if difficulty == Difficulty::Easy {
+        return actions.choose(rng).copied();
+    }
+    let replies = status.legal_actions(1 - player_id);
+    let mut best = Vec::new();
+    let mut best_score = f64::NEG_INFINITY;
+    for action in actions {
+        let outcomes: Vec<f64> = replies
+            .iter()
+            .map(|&reply| {
+                let mut actions = [action; 2];
+                actions[1 - player_id] = reply;
+                let mut next = status.clone();
+                match next.push_actions(actions) {
+                    Ok(_) => evaluate(&next, player_id),
+                    Err(_) => evaluate(status, player_id), // A collision replays the turn
+                }
+            })
+            .collect();
+        let score = match outcomes.is_empty() {
+            true => evaluate(status, player_id),
+            false => {
+                let average = outcomes.iter().sum::<f64>() / outcomes.len() as f64;
+                let worst = outcomes.iter().copied().fold(f64::INFINITY, f64::min);
+                match difficulty {
+                    Difficulty::Hard => worst + average / 100.0, // Average only breaks ties
+                    _ => average,
+                }
+            }
+        };
+        if score > best_score {
+            best_score = score;
+            best.clear();
+        }
+        if score == best_score {
+            best.push(action);
+        }
+    }
+    best.choose(rng).copied()
+}
+
+// How good the position is for `player_id`: health first, then room to move
+fn evaluate(status: &GameStatus, player_id: usize) -> f64 {
+    let other = 1 - player_id;
+    let health = status.player_stati.map(|status| status.health);
+    match (health[player_id], health[other]) {
+        (0, 0) => return -500.0,
+        (0, _) => return -1000.0,
+        (_, 0) => return 1000.0,
+        _ => {}
+    }
+    let mobility = [0, 1].map(|id| status.legal_actions(id).len() as f64);
+    if mobility[player_id] == 0.0 {
+        // Stuck players lose as soon as the turn resolves
+        return -800.0;
+    }
+    let health = health[player_id] as f64 - health[other] as f64;
+    health * 10.0 + (mobility[player_id] - mobility[other]) / 2.0
+}
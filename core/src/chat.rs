@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+pub const MAX_CHAT_LENGTH: usize = 200; // Characters
+pub const CHAT_RATE: f32 = 0.5; // Chat messages per second a connection can sustain
+pub const CHAT_BURST: f32 = 5.0; // Past this, the server drops chat messages
+
+// Something said in a room, either typed or picked from the quick emotes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ChatMessage {
+    Text(String),
+    Emote(Emote),
+}
+impl ChatMessage {
+    // With text trimmed, or `None` if it's empty, too long or has control characters
+    pub fn validate(self) -> Option<Self> {
+        match self {
+            ChatMessage::Text(text) => {
+                let text = text.trim();
+                let valid = !text.is_empty()
+                    && text.chars().count() <= MAX_CHAT_LENGTH
+                    && !text.chars().any(char::is_control);
+                valid.then(|| ChatMessage::Text(text.to_string()))
+            }
+            ChatMessage::Emote(emote) => Some(ChatMessage::Emote(emote)),
+        }
+    }
+}
+impl std::fmt::Display for ChatMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChatMessage::Text(text) => write!(f, "{}", text),
+            ChatMessage::Emote(emote) => write!(f, "{}", emote),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Emote {
+    Hello,
+    GoodLuck,
+    WellPlayed,
+    Oops,
+    Thanks,
+    GoodGame,
+}
+impl Emote {
+    pub const ALL: [Emote; 6] = [
+        Emote::Hello,
+        Emote::GoodLuck,
+        Emote::WellPlayed,
+        Emote::Oops,
+        Emote::Thanks,
+        Emote::GoodGame,
+    ];
+}
+impl std::fmt::Display for Emote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Emote::Hello => write!(f, "👋 Hello!"),
+            Emote::GoodLuck => write!(f, "🍀 Good luck!"),
+            Emote::WellPlayed => write!(f, "👏 Well played!"),
+            Emote::Oops => write!(f, "😬 Oops!"),
+            Emote::Thanks => write!(f, "🙏 Thanks!"),
+            Emote::GoodGame => write!(f, "🤝 Good game!"),
+        }
+    }
+}
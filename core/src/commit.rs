@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::PlayerAction;
+
+pub type Nonce = [u8; 16];
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Commitment([u8; 32]);
+impl Commitment {
+    // Binds the action to the player, so a commitment can't be copied from the opponent
+    pub fn new(player_id: u8, action: PlayerAction, nonce: Nonce) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update([player_id]);
+        hasher.update(serde_json::to_vec(&action).expect("Actions always serialise"));
+        hasher.update(nonce);
+        Self(hasher.finalize().into())
+    }
+
+    pub fn verify(&self, player_id: u8, action: PlayerAction, nonce: Nonce) -> bool {
+        *self == Self::new(player_id, action, nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ActionType, Direction, SpatialDirection, TemporalDirection};
+
+    fn action(spatial: SpatialDirection) -> PlayerAction {
+        PlayerAction {
+            direction: Direction {
+                spatial,
+                temporal: TemporalDirection::Forward,
+            },
+            action_type: ActionType::Move,
+        }
+    }
+
+    #[test]
+    fn verify_accepts_only_the_committed_action() {
+        let (action, other) = (action(SpatialDirection::Up), action(SpatialDirection::Down));
+        let nonce = [7; 16];
+        let commitment = Commitment::new(0, action, nonce);
+        assert!(commitment.verify(0, action, nonce));
+        assert!(!commitment.verify(0, other, nonce));
+        assert!(!commitment.verify(0, action, [8; 16]));
+    }
+
+    #[test]
+    fn verify_binds_the_player() {
+        let action = action(SpatialDirection::Up);
+        let nonce = [7; 16];
+        // The opponent can't replay someone else's commitment as their own
+        assert!(!Commitment::new(0, action, nonce).verify(1, action, nonce));
+        assert_ne!(
+            Commitment::new(0, action, nonce),
+            Commitment::new(1, action, nonce)
+        );
+    }
+}
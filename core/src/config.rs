@@ -0,0 +1,88 @@
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+// Bounds on rulesets asked for by players, so nobody can be matched into a turn time they can't
+// keep up with
+pub const TURN_TIMES: RangeInclusive<u32> = 5..=600; // Seconds
+pub const TIME_BANKS: RangeInclusive<u32> = 30..=3600; // Seconds
+pub const MAX_INCREMENT: u32 = 60; // Seconds
+pub const MAX_BEST_OF: u32 = 9;
+
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct RoomConfig {
+    pub commit_reveal: bool,    // Actions are committed as hashes, then revealed
+    pub turn_time: Option<u32>, // Seconds each player has to submit an action
+    pub timeout_action: TimeoutAction, // For players who run out of turn time
+    pub time_bank: Option<u32>, // Seconds of thinking time each player has per game
+    pub increment: u32,         // Seconds added to time banks after every turn
+    pub swap_spawns: bool,      // Whether players swap spawns for every rematch
+    pub best_of: Option<u32>,   // Number of games in a series
+}
+impl RoomConfig {
+    // `None` if any limit is outside the bounds servers accept from players
+    pub fn validate(self) -> Option<Self> {
+        let valid = self.turn_time.is_none_or(|secs| TURN_TIMES.contains(&secs))
+            && self.time_bank.is_none_or(|secs| TIME_BANKS.contains(&secs))
+            && self.increment <= MAX_INCREMENT
+            && self
+                .best_of
+                .is_none_or(|games| (1..=MAX_BEST_OF).contains(&games));
+        valid.then_some(self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum TimeoutAction {
+    #[default]
+    Wait, // Stay in place, preferring to move forward in time
+    Random,
+    Forfeit,
+}
+impl std::str::FromStr for TimeoutAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wait" => Ok(Self::Wait),
+            "random" => Ok(Self::Random),
+            "forfeit" => Ok(Self::Forfeit),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_bounds_every_limit() {
+        assert_eq!(
+            RoomConfig::default().validate(),
+            Some(RoomConfig::default())
+        );
+        let configs = [
+            RoomConfig {
+                turn_time: Some(1),
+                timeout_action: TimeoutAction::Forfeit,
+                ..Default::default()
+            },
+            RoomConfig {
+                time_bank: Some(0),
+                ..Default::default()
+            },
+            RoomConfig {
+                increment: MAX_INCREMENT + 1,
+                ..Default::default()
+            },
+            RoomConfig {
+                best_of: Some(0),
+                ..Default::default()
+            },
+        ];
+        for config in configs {
+            assert_eq!(config.validate(), None);
+        }
+    }
+}
@@ -4,6 +4,10 @@ use crate::{PlayerAction, PlayerStatus, LENGTH, SIZE};
 
 pub type DisplayData = [[[TileDisplayData; SIZE]; SIZE]; LENGTH];
 
+// A tile shows at most one player and one outgoing action
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TileTaken;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TileDisplayData {
     player: Option<(u8, bool, PlayerStatus)>, // Which player is here, their status, and whether this is their "current" position
@@ -11,6 +15,11 @@ pub struct TileDisplayData {
     outgoing: Option<PlayerAction>,           // What action is happening in this cell
     incoming: Vec<(u8, PlayerAction, bool)>, // What actions are about to affect this cell, who did them, and whether they are movement
 }
+impl Default for TileDisplayData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl TileDisplayData {
     pub const fn new() -> Self {
         Self {
@@ -26,17 +35,17 @@ impl TileDisplayData {
         player_id: u8,
         active: bool,
         status: PlayerStatus,
-    ) -> Result<(), ()> {
+    ) -> Result<(), TileTaken> {
         if self.player.is_some() {
-            return Err(());
+            return Err(TileTaken);
         }
         self.player = Some((player_id, active, status));
         Ok(())
     }
 
-    pub fn set_outgoing(&mut self, action: PlayerAction) -> Result<(), ()> {
+    pub fn set_outgoing(&mut self, action: PlayerAction) -> Result<(), TileTaken> {
         if self.outgoing.is_some() {
-            return Err(());
+            return Err(TileTaken);
         }
         self.outgoing = Some(action);
         Ok(())
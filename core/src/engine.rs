@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DisplayData, GameResult, GameStatus, PlayerAction, PlayerStatus, RequestId, RoomConfig, Stamp,
+};
+
+// Sent to a bot running as a separate process, one JSON object per line on its stdin. A game
+// is `Config`, then `Position` and `RequestMove` every turn, then `Result`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum EngineMessage {
+    Config(u8, RoomConfig), // The bot's seat, and the rules of the game about to start
+    Position(Box<Position>),
+    RequestMove(RequestId, u64), // With the milliseconds the bot has to answer
+    Result(GameResult),
+    Quit, // The bot should exit, and is killed if it doesn't
+}
+impl EngineMessage {
+    pub fn ser(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn deser(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+}
+
+// Read from a bot's stdout, one JSON object per line. Anything else it wants to print belongs
+// on stderr
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum EngineReply {
+    Move(RequestId, PlayerAction), // Echoing the id of the request it answers
+}
+impl EngineReply {
+    pub fn ser(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn deser(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+}
+
+// Everything a bot needs to choose its next action
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Position {
+    pub turn: usize,
+    pub spawns_swapped: bool,
+    pub history: Vec<[PlayerAction; 2]>, // Every resolved turn, to rebuild the game from
+    pub locations: [Stamp; 2],
+    pub stati: [PlayerStatus; 2],
+    pub legal_actions: Vec<PlayerAction>, // For the bot's own seat
+    pub display: DisplayData,
+}
+impl Position {
+    pub fn new(status: &GameStatus, player_id: usize, spawns_swapped: bool) -> Self {
+        let [first, second] = &status.player_actions;
+        Self {
+            turn: status.turn(),
+            spawns_swapped,
+            history: first.iter().zip(second).map(|(a, b)| [a.1, b.1]).collect(),
+            locations: status.player_locations,
+            stati: status.player_stati,
+            legal_actions: status.legal_actions(player_id),
+            display: status.display(),
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DisplayData, GameResult, GameStatus, InvalidMove, PlayerAction, RoomConfig, LENGTH,
+    MAX_REJECTED_TURNS, SIZE,
+};
+
+// Feature planes in an observation, each LENGTH x SIZE x SIZE. Planes come in pairs, the
+// observing player's first and then the opponent's, except for hazards
+pub const PLANES: usize = 13;
+pub const OBSERVATION_SHAPE: [usize; 4] = [PLANES, LENGTH, SIZE, SIZE];
+const MAX_HEALTH: f32 = 3.0;
+const MAX_IFRAMES: f32 = 3.0;
+
+const CURRENT: usize = 0; // Where each player is now
+const TRAIL: usize = 2; // Where each player has been on earlier turns
+const HEALTH: usize = 4; // Health out of 3 wherever each player is or has been
+const IFRAMES: usize = 6; // Invulnerable turns out of 3 left, likewise
+const HAZARD: usize = 8; // Tiles that deal damage
+const ATTACKS: usize = 9; // Attacks each player has aimed at a tile
+const MOVES: usize = 11; // Moves each player has made into a tile
+
+// What both players see after a reset or a step, from each of their points of view
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Observation {
+    pub planes: [Vec<f32>; 2], // Laid out as `OBSERVATION_SHAPE`, flattened
+    pub action_masks: [[bool; PlayerAction::COUNT]; 2], // Legal actions, by `PlayerAction::index`
+}
+
+// Details of a step beyond the rewards
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StepInfo {
+    pub turn: usize,
+    pub result: Option<GameResult>,
+    pub rejected: Option<[Option<InvalidMove>; 2]>, // Why the turn wasn't played, if it wasn't
+}
+
+// A game to train agents on, advanced by both players' actions at once. Each player gets a reward
+// of 1 for a win and -1 for a loss when the game ends, and 0 otherwise
+pub struct Environment {
+    status: GameStatus,
+    spawns_swapped: bool,
+    config: RoomConfig,
+    rng: StdRng,
+    result: Option<GameResult>,
+    rejected: u32,
+}
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Environment {
+    pub fn new() -> Self {
+        Self {
+            status: GameStatus::initial(),
+            spawns_swapped: false,
+            config: RoomConfig::default(),
+            rng: StdRng::seed_from_u64(0),
+            result: None,
+            rejected: 0,
+        }
+    }
+
+    // Starts a new game. With `swap_spawns` set in `config`, the seed decides which player
+    // starts where
+    pub fn reset(&mut self, seed: u64, config: RoomConfig) -> Observation {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.config = config;
+        self.spawns_swapped = config.swap_spawns && self.rng.gen();
+        self.status = match self.spawns_swapped {
+            true => GameStatus::swapped(),
+            false => GameStatus::initial(),
+        };
+        self.result = None;
+        self.rejected = 0;
+        self.observation()
+    }
+
+    // Plays a turn. A turn with an illegal action or a collision isn't played, as on the server,
+    // and the same players act again. The game ends as `GameStatus::stuck_result` says once a
+    // player has no legal action, and as a draw after `MAX_REJECTED_TURNS` rejected turns in a row
+    pub fn step(&mut self, actions: [PlayerAction; 2]) -> (Observation, [f32; 2], bool, StepInfo) {
+        let mut rejected = None;
+        if self.result.is_none() {
+            match self.status.push_actions(actions) {
+                Ok(result) => {
+                    self.rejected = 0;
+                    self.result = result.or_else(|| self.status.stuck_result());
+                }
+                Err(crate::Error::InvalidMove(reasons)) => {
+                    rejected = Some(reasons);
+                    self.rejected += 1;
+                    if self.rejected >= MAX_REJECTED_TURNS {
+                        self.result = Some(GameResult::Draw);
+                    }
+                }
+            }
+        }
+        let rewards = match self.result.and_then(|result| result.winner()) {
+            Some(0) => [1.0, -1.0],
+            Some(_) => [-1.0, 1.0],
+            None => [0.0, 0.0],
+        };
+        let info = StepInfo {
+            turn: self.status.turn(),
+            result: self.result,
+            rejected,
+        };
+        (self.observation(), rewards, self.result.is_some(), info)
+    }
+
+    pub fn status(&self) -> &GameStatus {
+        &self.status
+    }
+
+    pub fn spawns_swapped(&self) -> bool {
+        self.spawns_swapped
+    }
+
+    pub fn config(&self) -> RoomConfig {
+        self.config
+    }
+
+    pub fn observation(&self) -> Observation {
+        let display = self.status.display();
+        Observation {
+            planes: [0, 1].map(|player_id| encode(&display, player_id)),
+            action_masks: [0, 1].map(|player_id| self.action_mask(player_id)),
+        }
+    }
+
+    pub fn action_mask(&self, player_id: usize) -> [bool; PlayerAction::COUNT] {
+        let mut mask = [false; PlayerAction::COUNT];
+        for action in self.status.legal_actions(player_id) {
+            mask[action.index()] = true;
+        }
+        mask
+    }
+}
+
+// Feature planes for `player_id`, laid out as `OBSERVATION_SHAPE` and flattened
+pub fn encode(display: &DisplayData, player_id: usize) -> Vec<f32> {
+    let mut planes = vec![0.0; OBSERVATION_SHAPE.iter().product()];
+    // The observing player's plane of each pair comes first
+    let side = |id: u8| (id as usize != player_id) as usize;
+    for (t, slice) in display.iter().enumerate() {
+        for (y, row) in slice.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                let mut set = |plane: usize, value: f32| {
+                    planes[((plane * LENGTH + t) * SIZE + y) * SIZE + x] += value;
+                };
+                if let Some((id, active, status)) = tile.player() {
+                    let presence = if active { CURRENT } else { TRAIL };
+                    set(presence + side(id), 1.0);
+                    set(HEALTH + side(id), status.health as f32 / MAX_HEALTH);
+                    set(IFRAMES + side(id), status.iframes as f32 / MAX_IFRAMES);
+                }
+                if tile.is_attacked() {
+                    set(HAZARD, 1.0);
+                }
+                for &(id, _, attack) in tile.incoming() {
+                    let plane = if attack { ATTACKS } else { MOVES };
+                    set(plane + side(id), 1.0);
+                }
+            }
+        }
+    }
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bot_action, Board, Difficulty, PlayerStatus, TileDisplayData};
+
+    // The first legal action of each player, or an illegal one for players in `illegal`
+    fn actions(environment: &Environment, illegal: [bool; 2]) -> [PlayerAction; 2] {
+        std::array::from_fn(|player_id| {
+            let mask = environment.action_mask(player_id);
+            let index = (0..PlayerAction::COUNT).find(|&i| mask[i] != illegal[player_id]);
+            PlayerAction::from_index(index.expect("Both kinds of action exist")).unwrap()
+        })
+    }
+
+    fn at(plane: usize, t: usize, y: usize, x: usize) -> usize {
+        ((plane * LENGTH + t) * SIZE + y) * SIZE + x
+    }
+
+    #[test]
+    fn encode_lays_out_planes_per_observer() {
+        let mut display: DisplayData = std::array::from_fn(|_| {
+            std::array::from_fn(|_| std::array::from_fn(|_| TileDisplayData::new()))
+        });
+        let status = PlayerStatus {
+            health: 3,
+            iframes: 0,
+            time: 0,
+        };
+        let action = PlayerAction::from_index(0).unwrap();
+        display[1][2][3].set_player(0, true, status).unwrap();
+        display[0][4][5].set_player(1, false, status).unwrap();
+        display[1][2][4].add_incoming_attack(0, action);
+        display[0][4][4].add_incoming_move(1, action);
+
+        let planes = encode(&display, 0);
+        assert_eq!(planes.len(), PLANES * LENGTH * SIZE * SIZE);
+        let set: Vec<usize> = (0..planes.len()).filter(|&i| planes[i] != 0.0).collect();
+        let mut expected = vec![
+            at(CURRENT, 1, 2, 3),
+            at(HEALTH, 1, 2, 3),
+            at(TRAIL + 1, 0, 4, 5),
+            at(HEALTH + 1, 0, 4, 5),
+            at(HAZARD, 1, 2, 4),
+            at(ATTACKS, 1, 2, 4),
+            at(MOVES + 1, 0, 4, 4),
+        ];
+        expected.sort();
+        assert_eq!(set, expected);
+        assert_eq!(planes[at(HEALTH, 1, 2, 3)], 1.0);
+
+        // The other player sees the same board with the pairs swapped
+        let planes = encode(&display, 1);
+        assert_eq!(planes[at(CURRENT + 1, 1, 2, 3)], 1.0);
+        assert_eq!(planes[at(TRAIL, 0, 4, 5)], 1.0);
+        assert_eq!(planes[at(ATTACKS + 1, 1, 2, 4)], 1.0);
+        assert_eq!(planes[at(MOVES, 0, 4, 4)], 1.0);
+        assert_eq!(planes[at(HAZARD, 1, 2, 4)], 1.0);
+    }
+
+    #[test]
+    fn reset_starts_a_fresh_game_from_the_seed() {
+        let mut environment = Environment::new();
+        let config = RoomConfig {
+            swap_spawns: true,
+            ..Default::default()
+        };
+        let swapped: Vec<bool> = (0..16)
+            .map(|seed| {
+                environment.reset(seed, config);
+                environment.spawns_swapped()
+            })
+            .collect();
+        assert!(swapped.contains(&true) && swapped.contains(&false));
+        for (seed, &swapped) in swapped.iter().enumerate() {
+            environment.step(actions(&environment, [false; 2]));
+            let observation = environment.reset(seed as u64, config);
+            assert_eq!(environment.spawns_swapped(), swapped);
+            assert_eq!(environment.status().turn(), 0);
+            let [a, b] = Board::SPAWNS;
+            let spawns = if swapped { [b, a] } else { [a, b] };
+            assert_eq!(environment.status().player_locations, spawns);
+            assert_eq!(
+                observation.action_masks,
+                [0, 1].map(|i| environment.action_mask(i))
+            );
+        }
+        // Spawns stay put unless the config asks for swapping
+        environment.reset(
+            swapped.iter().position(|&s| s).unwrap() as u64,
+            RoomConfig::default(),
+        );
+        assert!(!environment.spawns_swapped());
+    }
+
+    #[test]
+    fn action_mask_matches_legal_actions() {
+        let environment = Environment::new();
+        for player_id in 0..2 {
+            let mask = environment.action_mask(player_id);
+            let legal = environment.status().legal_actions(player_id);
+            assert!(!legal.is_empty() && legal.len() < PlayerAction::COUNT);
+            for action in PlayerAction::all() {
+                assert_eq!(mask[action.index()], legal.contains(&action));
+            }
+        }
+    }
+
+    #[test]
+    fn rejected_turns_are_replayed_until_the_game_is_a_draw() {
+        let mut environment = Environment::new();
+        for turn in 1..=MAX_REJECTED_TURNS {
+            let (_, rewards, done, info) = environment.step(actions(&environment, [true, false]));
+            let reasons = info.rejected.expect("The illegal action is refused");
+            assert!(reasons[0].is_some() && reasons[1].is_none());
+            assert_eq!(info.turn, 0);
+            assert_eq!(rewards, [0.0; 2]);
+            assert_eq!(done, turn == MAX_REJECTED_TURNS);
+        }
+        assert_eq!(environment.status().turn(), 0);
+        let (_, _, done, info) = environment.step(actions(&environment, [false; 2]));
+        assert!(done);
+        assert_eq!(info.result, Some(GameResult::Draw));
+        assert_eq!(info.turn, 0);
+    }
+
+    #[test]
+    fn step_rewards_the_winner_once_the_game_ends() {
+        let mut environment = Environment::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        let (rewards, info) = loop {
+            let [_, fixed] = actions(&environment, [false; 2]);
+            let chosen = bot_action(environment.status(), 0, Difficulty::Hard, &mut rng);
+            let (_, rewards, done, info) = environment.step([chosen.unwrap(), fixed]);
+            if done {
+                break (rewards, info);
+            }
+            assert_eq!(rewards, [0.0; 2]);
+            assert!(info.turn < 1000, "The game should end");
+        };
+        // The hard bot outplays an opponent that always takes its first legal action
+        assert_eq!(info.result, Some(GameResult::Win(0)));
+        assert_eq!(rewards, [1.0, -1.0]);
+        // Finished games stay finished
+        let (_, again, done, next) = environment.step(actions(&environment, [false; 2]));
+        assert!(done);
+        assert_eq!((again, next.result), (rewards, info.result));
+    }
+}
@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{
-    Board, DisplayData, Error, Impact, PlayerAction, Stamp, TileDisplayData, LENGTH, SIZE,
-};
+use crate::{Board, DisplayData, Error, Impact, InvalidMove, PlayerAction, Stamp, TileDisplayData};
 
+// Turns in a row that fail to resolve before a game between programs is called a draw. Players on
+// the server choose again instead, with the turn timer and clocks to keep the game moving
+pub const MAX_REJECTED_TURNS: u32 = 20;
+
+#[derive(Clone)]
 pub struct GameStatus {
     pub board: Board,
     pub player_actions: [Vec<(Stamp, PlayerAction, Impact)>; 2],
@@ -12,11 +15,47 @@ pub struct GameStatus {
 }
 impl GameStatus {
     pub fn initial() -> Self {
+        Self::with_spawns(Board::SPAWNS)
+    }
+
+    pub fn with_spawns(spawns: [Stamp; 2]) -> Self {
         Self {
-            board: Board::initial(),
+            board: Board::with_spawns(spawns),
             player_actions: [const { Vec::new() }; 2],
             player_stati: [PlayerStatus::new(); 2],
-            player_locations: Board::SPAWNS,
+            player_locations: spawns,
+        }
+    }
+
+    // Player 1 starts where player 0 normally would, and vice versa
+    pub fn swapped() -> Self {
+        let [a, b] = Board::SPAWNS;
+        Self::with_spawns([b, a])
+    }
+
+    pub fn turn(&self) -> usize {
+        self.player_actions[0].len()
+    }
+
+    // Actions that pass validation on their own; they may still collide with the opponent's
+    pub fn legal_actions(&self, player_id: usize) -> Vec<PlayerAction> {
+        PlayerAction::all()
+            .filter(|&action| {
+                self.board
+                    .calculate_action(player_id, action, self.player_locations[player_id])
+                    .is_ok()
+            })
+            .collect()
+    }
+
+    // A player left without a legal action loses, and a draw if neither has one
+    pub fn stuck_result(&self) -> Option<GameResult> {
+        let stuck = [0, 1].map(|player_id| self.legal_actions(player_id).is_empty());
+        match stuck {
+            [true, true] => Some(GameResult::Draw),
+            [true, false] => Some(GameResult::Win(1)),
+            [false, true] => Some(GameResult::Win(0)),
+            [false, false] => None,
         }
     }
 
@@ -24,14 +63,23 @@ impl GameStatus {
         &mut self,
         actions: [PlayerAction; 2],
     ) -> Result<Option<GameResult>, Error> {
-        let mut results = actions.iter().enumerate().map(|(player_id, &action)| {
-            self.board
-                .calculate_action(player_id, action, self.player_locations[player_id])
+        let results: [Result<(Stamp, Impact), InvalidMove>; 2] = std::array::from_fn(|player_id| {
+            self.board.calculate_action(
+                player_id,
+                actions[player_id],
+                self.player_locations[player_id],
+            )
         });
-        let results = [results.next().expect("oof")?, results.next().expect("oof")?];
+        let results = match results {
+            [Ok(a), Ok(b)] => [a, b],
+            [a, b] => return Err(Error::InvalidMove([a.err(), b.err()])),
+        };
         if results[0].1.player.0 == results[1].1.player.0 {
             // filling the same tile
-            return Err(Error::InvalidMove("Bonked lmao"));
+            return Err(Error::InvalidMove([
+                Some(InvalidMove::Collision(1)),
+                Some(InvalidMove::Collision(0)),
+            ]));
         }
         for (i, &e) in results.iter().enumerate() {
             self.player_actions[i].push((e.0, actions[i], e.1));
@@ -62,7 +110,7 @@ impl GameStatus {
             if board.get(stamp).expect("Presumed valid").is_hazard() {
                 player_stati[player_id].damage();
             }
-            board.set_status(stamp, Some(player_stati[player_id].clone()));
+            board.set_status(stamp, Some(player_stati[player_id]));
             if tick {
                 player_stati[player_id].tick();
             }
@@ -120,6 +168,24 @@ impl GameStatus {
         data
     }
 }
+impl std::fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Turn {}", self.turn())?;
+        for player_id in 0..2 {
+            let PlayerStatus {
+                health, iframes, ..
+            } = self.player_stati[player_id];
+            let Stamp { x, y, t } = self.player_locations[player_id];
+            writeln!(
+                f,
+                "Player {}: {} health, {} iframes, at ({}, {}, {})",
+                player_id, health, iframes, x, y, t
+            )?;
+        }
+        writeln!(f)?;
+        write!(f, "{}", self.board)
+    }
+}
 
 pub enum TurnStatus {
     Active(u8),
@@ -131,6 +197,11 @@ pub struct PlayerStatus {
     pub iframes: u8,
     pub time: usize,
 }
+impl Default for PlayerStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl PlayerStatus {
     pub const fn new() -> Self {
         Self {
@@ -148,7 +219,8 @@ impl PlayerStatus {
 
     pub fn damage(&mut self) {
         if self.is_vulnerable() {
-            self.health -= 1;
+            // Hazards placed later in the game can hit a player who was already defeated
+            self.health = self.health.saturating_sub(1);
             self.iframes = 3;
         }
     }
@@ -165,4 +237,62 @@ impl PlayerStatus {
 pub enum GameResult {
     Win(u8),
     Draw,
+    Timeout(u8),     // The player whose time bank ran out
+    Resignation(u8), // The player who resigned
+    AgreedDraw,
+}
+impl GameResult {
+    pub fn winner(&self) -> Option<u8> {
+        match *self {
+            GameResult::Win(player_id) => Some(player_id),
+            GameResult::Draw | GameResult::AgreedDraw => None,
+            GameResult::Timeout(player_id) | GameResult::Resignation(player_id) => {
+                Some(1 - player_id)
+            }
+        }
+    }
+}
+impl std::fmt::Display for GameResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let score = match self.winner() {
+            Some(0) => "1-0",
+            Some(_) => "0-1",
+            None => "1/2-1/2",
+        };
+        match self {
+            GameResult::Win(_) | GameResult::Draw => write!(f, "{}", score),
+            GameResult::Timeout(_) => write!(f, "{} (timeout)", score),
+            GameResult::Resignation(_) => write!(f, "{} (resignation)", score),
+            GameResult::AgreedDraw => write!(f, "{} (agreed)", score),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: [u32; 2],
+    pub draws: u32,
+    pub best_of: Option<u32>,
+}
+impl MatchScore {
+    pub fn new(best_of: Option<u32>) -> Self {
+        Self {
+            wins: [0; 2],
+            draws: 0,
+            best_of,
+        }
+    }
+
+    pub fn record(&mut self, result: GameResult) {
+        match result.winner() {
+            Some(player_id) => self.wins[player_id as usize] += 1,
+            None => self.draws += 1,
+        }
+    }
+
+    // The player who has won the series, if it's over
+    pub fn winner(&self) -> Option<u8> {
+        let best_of = self.best_of?;
+        (0..2).find(|&i| self.wins[i as usize] > best_of / 2)
+    }
 }
@@ -1,13 +1,29 @@
 mod board;
+mod bot;
+mod chat;
+mod commit;
+mod config;
 mod display;
+mod engine;
+mod environment;
 mod game;
 mod network;
 mod player;
+mod profile;
+mod replay;
 mod space;
 
 pub use board::*;
+pub use bot::*;
+pub use chat::*;
+pub use commit::*;
+pub use config::*;
 pub use display::*;
+pub use engine::*;
+pub use environment::*;
 pub use game::*;
 pub use network::*;
 pub use player::*;
+pub use profile::*;
+pub use replay::*;
 pub use space::*;
@@ -1,20 +1,161 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
-use crate::{DisplayData, GameResult, PlayerAction, PlayerStatus};
+use crate::{
+    ChatMessage, Commitment, Difficulty, DisplayData, GameResult, InvalidMove, MatchScore, Nonce,
+    PlayerAction, PlayerStatus, Profile, Replay, RoomConfig,
+};
+
+// Chosen by the client, and echoed back in the response to a request
+pub type RequestId = u32;
+pub type RoomId = u32;
+pub type RejoinToken = u64; // Handed out with a seat, to reclaim it after losing the connection
+
+// What a player looking for a match is willing to play
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MatchPreferences {
+    pub ruleset: Option<RoomConfig>, // The server's default rules if `None`, matched exactly
+    pub max_rating_gap: Option<u32>, // How much stronger or weaker the opponent may be
+    pub rated: bool,                 // Only paired with players who chose the same
+    pub bot: Option<Difficulty>,     // Plays a server-side bot right away instead, unrated
+}
 
+// Messages sent from a client to the server
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum NetBlob {
-    Join,
-    Assign(u8),
-    Action(PlayerAction),
+pub enum ClientMessage {
+    FindMatch(RequestId, String, MatchPreferences), // With a name, answered by `Assign` once matched
+    Rejoin(RequestId, RoomId, RejoinToken), // With the token of the last `Assign`, answered by a new one
+    CancelMatch,
+    Leaderboard(RequestId),
     Leave,
-    Display(DisplayData),
+    Action(RequestId, PlayerAction),
+    Commit(RequestId, Commitment),
+    Reveal(RequestId, PlayerAction, Nonce),
+    Ready,
+    Unready,
+    OfferRematch,
+    AcceptRematch,
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    Pause,
+    Resume,
+    Chat(ChatMessage),
+}
+
+// Messages sent from the server to a client
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ServerMessage {
+    Assign(RequestId, RoomId, u8, RejoinToken),
+    Display(Box<DisplayData>),
     Result(GameResult),
     Stati([PlayerStatus; 2]),
-    Start,
-    InvalidMove,
+    Start(bool), // Whether the spawns are swapped this game
+    InvalidMove(Option<RequestId>, InvalidMove), // No id for moves the server submitted
+    ActionAccepted(RequestId),
+    PlayerReady(u8),
+    Config(RoomConfig),
+    RevealRequested,
+    Replay(Replay),
+    TurnTimer(u64), // Milliseconds left in the current turn
+    Clocks([ClockStatus; 2]),
+    LobbyReady([bool; 2]),
+    Phase(RoomPhase),
+    Countdown(u64), // Milliseconds until the game starts
+    RematchOffers([bool; 2]),
+    Score(MatchScore),
+    DrawOffers([bool; 2]),
+    PauseRequests([bool; 2]), // Who wants to pause, or resume while paused
+    Connected([bool; 2]),     // Which seats have a live connection
+    ProtocolError(Option<RequestId>, ProtocolError),
+    Announcement(String),                 // From the server operator
+    ServerShutdown,                       // Sent before the server closes every connection
+    QueuePosition(usize),                 // Starting at 1, sent whenever it changes
+    MatchCancelled,                       // Left the queue without a match
+    Profiles([Option<Profile>; 2]),       // Of the players in each seat
+    Leaderboard(RequestId, Vec<Profile>), // The highest rated players, best first
+    Chat(u8, ChatMessage),                // From the player in the given seat
+    Turn([PlayerAction; 2]),              // Both actions of the turn that just resolved
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    Malformed,     // Not a valid client message
+    TooLarge,      // Over MAX_MESSAGE_SIZE
+    RateLimited,   // Sending messages too quickly, the message was dropped
+    NotJoined,     // The message needs a seat
+    AlreadyJoined, // Already in a room or the queue
+    NotQueued,
+    InvalidName,    // Empty, too long or with control characters
+    NameTaken,      // Another connection is playing under the name
+    InvalidChat,    // Empty, too long or with control characters
+    InvalidRuleset, // A limit outside the bounds the server accepts
+    NoSeatHeld,     // The room has no seat for the token, or it was given up
+}
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::Malformed => write!(f, "malformed message"),
+            ProtocolError::TooLarge => write!(f, "message too large"),
+            ProtocolError::RateLimited => write!(f, "too many messages"),
+            ProtocolError::NotJoined => write!(f, "not in a seat"),
+            ProtocolError::AlreadyJoined => write!(f, "already in a room or the queue"),
+            ProtocolError::NotQueued => write!(f, "not in the queue"),
+            ProtocolError::InvalidName => write!(f, "invalid name"),
+            ProtocolError::NameTaken => write!(f, "the name is in use"),
+            ProtocolError::InvalidChat => write!(f, "invalid chat message"),
+            ProtocolError::InvalidRuleset => write!(f, "invalid ruleset"),
+            ProtocolError::NoSeatHeld => write!(f, "the seat is no longer held"),
+        }
+    }
+}
+
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2); // How often each side pings
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10); // Silence before a connection is dropped
+pub const MAX_MESSAGE_SIZE: usize = 4096; // Bytes, for messages from clients
+pub const LEADERBOARD_SIZE: usize = 20; // Players sent in response to a leaderboard request
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ClockStatus {
+    pub remaining: u64, // Milliseconds left in the player's time bank
+    pub running: bool,
 }
-impl NetBlob {
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RoomPhase {
+    Waiting,    // Not enough players
+    ReadyCheck, // Both seats taken, waiting for both players to ready up
+    Countdown,  // Both players ready, the game is about to start
+    InProgress,
+    Paused,
+    Finished,
+}
+
+impl ClientMessage {
+    // The id of a message expecting a response
+    pub fn request_id(&self) -> Option<RequestId> {
+        match self {
+            ClientMessage::FindMatch(request_id, _, _)
+            | ClientMessage::Rejoin(request_id, _, _)
+            | ClientMessage::Action(request_id, _)
+            | ClientMessage::Leaderboard(request_id)
+            | ClientMessage::Commit(request_id, _)
+            | ClientMessage::Reveal(request_id, _, _) => Some(*request_id),
+            _ => None,
+        }
+    }
+
+    pub fn ser(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn deser(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+}
+
+impl ServerMessage {
     pub fn ser(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
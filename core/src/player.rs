@@ -1,15 +1,95 @@
 use serde::{Deserialize, Serialize};
 
-use crate::Direction;
+use crate::{Direction, SpatialDirection, TemporalDirection};
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 pub struct PlayerAction {
     pub direction: Direction,
     pub action_type: ActionType,
 }
+// Compact notation, e.g. `MU+` moves up and forward in time, `AL-` attacks left and back in time
+impl std::fmt::Display for PlayerAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action_type = match self.action_type {
+            ActionType::Move => 'M',
+            ActionType::Attack => 'A',
+        };
+        let spatial = match self.direction.spatial {
+            SpatialDirection::Left => 'L',
+            SpatialDirection::Right => 'R',
+            SpatialDirection::Up => 'U',
+            SpatialDirection::Down => 'D',
+        };
+        let temporal = match self.direction.temporal {
+            TemporalDirection::Forward => '+',
+            TemporalDirection::Backward => '-',
+        };
+        write!(f, "{}{}{}", action_type, spatial, temporal)
+    }
+}
+impl std::str::FromStr for PlayerAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::all().find(|action| action.to_string() == s).ok_or(())
+    }
+}
+impl PlayerAction {
+    pub const COUNT: usize =
+        ActionType::ALL.len() * TemporalDirection::ALL.len() * SpatialDirection::ALL.len();
+
+    pub fn all() -> impl Iterator<Item = Self> {
+        ActionType::ALL.into_iter().flat_map(|action_type| {
+            TemporalDirection::ALL
+                .into_iter()
+                .flat_map(move |temporal| {
+                    SpatialDirection::ALL.into_iter().map(move |spatial| Self {
+                        direction: Direction { spatial, temporal },
+                        action_type,
+                    })
+                })
+        })
+    }
+
+    // Position in `all()`, e.g. for indexing a policy's outputs
+    pub fn index(&self) -> usize {
+        Self::all()
+            .position(|action| action == *self)
+            .expect("Every action is in all()")
+    }
+
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::all().nth(index)
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ActionType {
     Move,
     Attack,
 }
+impl ActionType {
+    pub const ALL: [Self; 2] = [Self::Move, Self::Attack];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notation_round_trips() {
+        for action in PlayerAction::all() {
+            assert_eq!(action.to_string().parse(), Ok(action));
+        }
+        assert_eq!("MU".parse::<PlayerAction>(), Err(()));
+        assert_eq!("XU+".parse::<PlayerAction>(), Err(()));
+    }
+
+    #[test]
+    fn notation_is_unique() {
+        let notation: std::collections::HashSet<String> = PlayerAction::all()
+            .map(|action| action.to_string())
+            .collect();
+        assert_eq!(notation.len(), PlayerAction::all().count());
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use crate::GameResult;
+
+pub const MAX_NAME_LENGTH: usize = 24; // Characters
+pub const INITIAL_RATING: u32 = 1500;
+const K_FACTOR: f64 = 32.0; // The most a rating can change in one game
+
+// A player's record across all their games on a server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub games_played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    #[serde(default = "initial_rating")] // Profiles from before ratings existed
+    pub rating: u32,
+    #[serde(default)]
+    pub rated_games: u32,
+}
+impl Profile {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            games_played: 0,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            rating: INITIAL_RATING,
+            rated_games: 0,
+        }
+    }
+
+    // Records a game the profile played as `player_id`
+    pub fn record(&mut self, player_id: u8, result: GameResult) {
+        self.games_played += 1;
+        match result.winner() {
+            Some(winner) if winner == player_id => self.wins += 1,
+            Some(_) => self.losses += 1,
+            None => self.draws += 1,
+        }
+    }
+
+    // Trimmed, or `None` if the name is empty, too long or has control characters
+    pub fn valid_name(name: &str) -> Option<&str> {
+        let name = name.trim();
+        let valid = !name.is_empty()
+            && name.chars().count() <= MAX_NAME_LENGTH
+            && !name.chars().any(char::is_control);
+        valid.then_some(name)
+    }
+}
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} [{}] ({}-{}-{})",
+            self.name, self.rating, self.wins, self.losses, self.draws
+        )
+    }
+}
+
+fn initial_rating() -> u32 {
+    INITIAL_RATING
+}
+
+// Both players' Elo ratings after a game between them
+pub fn rate(ratings: [u32; 2], result: GameResult) -> [u32; 2] {
+    let score = match result.winner() {
+        Some(0) => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    };
+    let difference = ratings[1] as f64 - ratings[0] as f64;
+    let expected = 1.0 / (1.0 + 10f64.powf(difference / 400.0));
+    let change = K_FACTOR * (score - expected);
+    let apply = |rating: u32, change: f64| (rating as f64 + change).round().max(0.0) as u32;
+    [apply(ratings[0], change), apply(ratings[1], -change)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_moves_equal_ratings_by_half_the_k_factor() {
+        let equal = [INITIAL_RATING; 2];
+        let (up, down) = (INITIAL_RATING + 16, INITIAL_RATING - 16);
+        assert_eq!(rate(equal, GameResult::Win(0)), [up, down]);
+        assert_eq!(rate(equal, GameResult::Resignation(0)), [down, up]);
+        assert_eq!(rate(equal, GameResult::Draw), equal);
+    }
+
+    #[test]
+    fn rate_is_symmetric_between_seats() {
+        let ratings = [1620, 1480];
+        for (result, swapped) in [
+            (GameResult::Win(0), GameResult::Win(1)),
+            (GameResult::Timeout(0), GameResult::Timeout(1)),
+            (GameResult::Draw, GameResult::Draw),
+        ] {
+            let [a, b] = rate(ratings, result);
+            assert_eq!(rate([ratings[1], ratings[0]], swapped), [b, a]);
+            // Points move from one player to the other
+            assert_eq!(a + b, ratings[0] + ratings[1]);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Commitment, GameResult, Nonce, PlayerAction, RoomConfig};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub config: RoomConfig,
+    #[serde(default)]
+    pub spawns_swapped: bool,
+    pub turns: Vec<ReplayTurn>,
+    #[serde(default)]
+    pub events: Vec<(usize, ReplayEvent)>, // Events, and the turn they happened before
+    pub result: Option<GameResult>,
+}
+impl Replay {
+    pub fn new(config: RoomConfig) -> Self {
+        Self {
+            config,
+            spawns_swapped: false,
+            turns: Vec::new(),
+            events: Vec::new(),
+            result: None,
+        }
+    }
+
+    pub fn push(&mut self, turn: ReplayTurn) {
+        self.turns.push(turn);
+    }
+
+    pub fn push_event(&mut self, event: ReplayEvent) {
+        self.events.push((self.turns.len(), event));
+    }
+
+    pub fn notation(&self) -> String {
+        let mut out = String::new();
+        let write_events = |out: &mut String, turn: usize| {
+            for (_, event) in self.events.iter().filter(|(t, _)| *t == turn) {
+                writeln!(out, "{{{}}}", event).unwrap();
+            }
+        };
+        for (turn, record) in self.turns.iter().enumerate() {
+            write_events(&mut out, turn);
+            let [a, b] = record.actions;
+            writeln!(out, "{}. {} {}", turn + 1, a, b).unwrap();
+        }
+        write_events(&mut out, self.turns.len());
+        if let Some(result) = self.result {
+            writeln!(out, "{}", result).unwrap();
+        }
+        out
+    }
+
+    // Checks every recorded commitment against the action that was actually played
+    pub fn verify(&self) -> bool {
+        self.turns.iter().all(|record| {
+            (0..2).all(|player_id| match record.commitments[player_id] {
+                Some((commitment, nonce)) => {
+                    commitment.verify(player_id as u8, record.actions[player_id], nonce)
+                }
+                // Only actions the server chose after a timeout go without a commitment
+                None => !self.config.commit_reveal || record.timed_out[player_id],
+            })
+        })
+    }
+
+    pub fn ser(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn deser(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReplayEvent {
+    DrawOffered(u8),
+    Paused,
+    Resumed,
+}
+impl std::fmt::Display for ReplayEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayEvent::DrawOffered(player_id) => {
+                write!(f, "Draw offered by player {}", player_id)
+            }
+            ReplayEvent::Paused => write!(f, "Paused"),
+            ReplayEvent::Resumed => write!(f, "Resumed"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct ReplayTurn {
+    pub actions: [PlayerAction; 2],
+    pub commitments: [Option<(Commitment, Nonce)>; 2],
+    #[serde(default)]
+    pub timed_out: [bool; 2], // Players whose action the server chose when their time ran out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ActionType, Direction, SpatialDirection, TemporalDirection};
+
+    fn action(spatial: SpatialDirection) -> PlayerAction {
+        PlayerAction {
+            direction: Direction {
+                spatial,
+                temporal: TemporalDirection::Forward,
+            },
+            action_type: ActionType::Move,
+        }
+    }
+
+    fn committed_turn(actions: [PlayerAction; 2]) -> ReplayTurn {
+        let nonces = [[1; 16], [2; 16]];
+        let commitments = [0, 1].map(|player_id| {
+            let commitment =
+                Commitment::new(player_id as u8, actions[player_id], nonces[player_id]);
+            Some((commitment, nonces[player_id]))
+        });
+        ReplayTurn {
+            actions,
+            commitments,
+            timed_out: [false; 2],
+        }
+    }
+
+    fn commit_reveal() -> Replay {
+        Replay::new(RoomConfig {
+            commit_reveal: true,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn verify_accepts_honest_turns() {
+        let mut replay = commit_reveal();
+        replay.push(committed_turn([
+            action(SpatialDirection::Up),
+            action(SpatialDirection::Down),
+        ]));
+        assert!(replay.verify());
+    }
+
+    #[test]
+    fn verify_rejects_a_changed_action() {
+        let mut replay = commit_reveal();
+        let mut turn =
+            committed_turn([action(SpatialDirection::Up), action(SpatialDirection::Down)]);
+        turn.actions[1] = action(SpatialDirection::Left);
+        replay.push(turn);
+        assert!(!replay.verify());
+    }
+
+    #[test]
+    fn verify_accepts_timed_out_turns() {
+        let mut replay = commit_reveal();
+        // A timed out player's action is submitted by the server without a commitment
+        let mut turn =
+            committed_turn([action(SpatialDirection::Up), action(SpatialDirection::Down)]);
+        turn.commitments[1] = None;
+        turn.timed_out[1] = true;
+        replay.push(turn);
+        assert!(replay.verify());
+    }
+
+    #[test]
+    fn verify_rejects_missing_commitments_otherwise() {
+        let mut replay = commit_reveal();
+        let mut turn =
+            committed_turn([action(SpatialDirection::Up), action(SpatialDirection::Down)]);
+        turn.commitments[0] = None;
+        turn.timed_out[1] = true;
+        replay.push(turn);
+        assert!(!replay.verify());
+        // Without commit-reveal, nothing is committed to begin with
+        replay.config.commit_reveal = false;
+        assert!(replay.verify());
+    }
+}
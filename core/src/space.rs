@@ -13,9 +13,15 @@ pub enum SpatialDirection {
     Up,
     Down,
 }
+impl SpatialDirection {
+    pub const ALL: [Self; 4] = [Self::Left, Self::Right, Self::Up, Self::Down];
+}
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TemporalDirection {
     Forward,
     Backward,
 }
+impl TemporalDirection {
+    pub const ALL: [Self; 2] = [Self::Forward, Self::Backward];
+}
@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+use neurojam24_core::ClockStatus;
+
+pub struct Clock {
+    remaining: Duration,
+    running_since: Option<Instant>,
+}
+impl Clock {
+    pub fn new(time_bank: Duration) -> Self {
+        Self {
+            remaining: time_bank,
+            running_since: None,
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.running_since.is_none() {
+            self.running_since = Some(Instant::now());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(running_since) = self.running_since.take() {
+            self.remaining = self.remaining.saturating_sub(running_since.elapsed());
+        }
+    }
+
+    pub fn add(&mut self, increment: Duration) {
+        self.remaining += increment;
+    }
+
+    pub fn remaining(&self) -> Duration {
+        match self.running_since {
+            Some(running_since) => self.remaining.saturating_sub(running_since.elapsed()),
+            None => self.remaining,
+        }
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    pub fn status(&self) -> ClockStatus {
+        ClockStatus {
+            remaining: self.remaining().as_millis() as u64,
+            running: self.running_since.is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stopped_clocks_keep_their_time() {
+        let bank = Duration::from_secs(60);
+        let mut clock = Clock::new(bank);
+        clock.start();
+        assert!(clock.status().running);
+        std::thread::sleep(Duration::from_millis(20));
+        clock.stop();
+        let remaining = clock.remaining();
+        assert!(remaining <= bank - Duration::from_millis(20));
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(clock.remaining(), remaining);
+        assert!(!clock.status().running);
+    }
+
+    #[test]
+    fn increments_unflag_empty_clocks() {
+        let mut clock = Clock::new(Duration::ZERO);
+        assert!(clock.is_flagged());
+        clock.add(Duration::from_secs(5));
+        assert!(!clock.is_flagged());
+        assert_eq!(clock.remaining(), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    io::BufRead,
+    sync::{Arc, Mutex},
+};
+
+use crate::Server;
+
+const HELP: &str = "\
+Commands:
+  rooms            List rooms
+  connections      List connections
+  queue            List players waiting for a match
+  kick <id>        Disconnect a connection
+  reset <room>     Discard a room's game and go back to the ready check
+  dump <room>      Print a room's game state
+  announce <text>  Send a message to every connection
+  shutdown         Disconnect everyone and stop the server
+  help             Show this message";
+
+// Reads admin commands from stdin until it closes or the server shuts down
+pub fn run(server: Arc<Mutex<Server>>) {
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let mut server = server.lock().unwrap();
+        match command {
+            "" => {}
+            "rooms" => print_rooms(&server),
+            "connections" => print_connections(&server),
+            "queue" => print_queue(&server),
+            "kick" => match args.trim().parse() {
+                Ok(connection_id) if server.kick(connection_id) => {
+                    println!("Kicking connection #{}", connection_id)
+                }
+                Ok(connection_id) => println!("No connection #{}", connection_id),
+                Err(_) => println!("Usage: kick <id>"),
+            },
+            "reset" => match args.trim().parse() {
+                Ok(room_id) => match server.rooms.get_mut(&room_id) {
+                    Some(room) => {
+                        println!("Resetting room {}", room_id);
+                        room.force_reset();
+                        server.flush();
+                        server.close_abandoned();
+                    }
+                    None => println!("No room {}", room_id),
+                },
+                Err(_) => println!("Usage: reset <room>"),
+            },
+            "dump" => match args.trim().parse() {
+                Ok(room_id) => match server.rooms.get(&room_id) {
+                    Some(room) => println!("{}", room.game_status),
+                    None => println!("No room {}", room_id),
+                },
+                Err(_) => println!("Usage: dump <room>"),
+            },
+            "announce" if !args.trim().is_empty() => server.announce(args.trim()),
+            "announce" => println!("Usage: announce <text>"),
+            "shutdown" => {
+                println!("Shutting down");
+                server.shutdown();
+                return;
+            }
+            "help" => println!("{}", HELP),
+            _ => println!("Unknown command '{}', try 'help'", command),
+        }
+    }
+}
+
+fn print_rooms(server: &Server) {
+    if server.rooms.is_empty() {
+        println!("No rooms");
+    }
+    for room in server.rooms.values() {
+        let seats = room.seats.iter().filter(|seat| seat.is_some()).count();
+        print!(
+            "Room {}: {}, {:?}, turn {}, {}/2 players, score {}-{}",
+            room.id,
+            if room.rated { "rated" } else { "unrated" },
+            room.phase,
+            room.game_status.turn(),
+            seats,
+            room.score.wins[0],
+            room.score.wins[1]
+        );
+        match room.result {
+            Some(result) => println!(", result {}", result),
+            None => println!(),
+        }
+    }
+}
+
+fn print_queue(server: &Server) {
+    if server.queue.entries().is_empty() {
+        println!("Nobody is waiting");
+    }
+    for (index, entry) in server.queue.entries().iter().enumerate() {
+        println!(
+            "{}. #{}, rating {}, {}, waiting for {}s",
+            index + 1,
+            entry.connection_id,
+            entry.rating,
+            if entry.preferences.rated {
+                "rated"
+            } else {
+                "unrated"
+            },
+            entry.queued_at.elapsed().as_secs()
+        );
+    }
+}
+
+fn print_connections(server: &Server) {
+    if server.connections.is_empty() {
+        println!("No connections");
+    }
+    let mut connections: Vec<_> = server.connections.iter().collect();
+    connections.sort_by_key(|(&connection_id, _)| connection_id);
+    for (&connection_id, connection) in connections {
+        let seat = match connection.seat {
+            Some((room_id, player_id)) => format!("room {}, player {}", room_id, player_id),
+            None if server.queue.contains(connection_id) => "queued".to_string(),
+            None => "no seat".to_string(),
+        };
+        println!(
+            "#{} {} {}({}), connected for {}s",
+            connection_id,
+            connection.addr,
+            connection
+                .name
+                .as_ref()
+                .map(|name| format!("{} ", name))
+                .unwrap_or_default(),
+            seat,
+            connection.connected_at.elapsed().as_secs()
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Instant;
+
+// Token bucket limiting how quickly a connection can send messages
+pub struct RateLimiter {
+    tokens: f32,
+    burst: f32,
+    rate: f32, // Tokens regained per second
+    last_update: Instant,
+}
+impl RateLimiter {
+    pub fn new(burst: f32, rate: f32) -> Self {
+        Self {
+            tokens: burst,
+            burst,
+            rate,
+            last_update: Instant::now(),
+        }
+    }
+
+    // Takes a token if one is available
+    pub fn try_take(&mut self) -> bool {
+        let now = Instant::now();
+        let regained = now.duration_since(self.last_update).as_secs_f32() * self.rate;
+        self.tokens = (self.tokens + regained).min(self.burst);
+        self.last_update = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_are_allowed_up_to_the_limit() {
+        let mut limiter = RateLimiter::new(3.0, 0.0);
+        assert!((0..3).all(|_| limiter.try_take()));
+        assert!(!limiter.try_take());
+    }
+
+    #[test]
+    fn tokens_come_back_over_time() {
+        let mut limiter = RateLimiter::new(1.0, 100.0);
+        assert!(limiter.try_take());
+        assert!(!limiter.try_take());
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(limiter.try_take());
+    }
+}
@@ -0,0 +1,32 @@
+use std::io::IsTerminal;
+
+use tracing_appender::{non_blocking::WorkerGuard, rolling};
+use tracing_subscriber::EnvFilter;
+
+// Installs the global subscriber. Levels come from `RUST_LOG` (info by default), `json` switches
+// to one JSON object per line, and `log_dir` writes rotated files instead of stdout.
+// The guard has to be kept alive for buffered lines to be written
+pub fn init(json: bool, log_dir: Option<&str>, rotation: &str) -> WorkerGuard {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let (writer, guard) = match log_dir {
+        Some(dir) => {
+            let appender = match rotation {
+                "hourly" => rolling::hourly(dir, "server.log"),
+                "daily" => rolling::daily(dir, "server.log"),
+                "never" => rolling::never(dir, "server.log"),
+                _ => panic!("Invalid log rotation, expected hourly, daily or never"),
+            };
+            tracing_appender::non_blocking(appender)
+        }
+        None => tracing_appender::non_blocking(std::io::stdout()),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(log_dir.is_none() && std::io::stdout().is_terminal());
+    match json {
+        true => builder.json().init(),
+        false => builder.init(),
+    }
+    guard
+}
@@ -1,185 +1,991 @@
 use std::{
-    net::TcpListener,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap},
+    io::ErrorKind,
+    net::{SocketAddr, TcpListener, TcpStream},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     thread::spawn,
+    time::{Duration, Instant},
 };
 
-use neurojam24_core::{GameResult, GameStatus, NetBlob, PlayerAction};
-use tungstenite::{accept, Message};
+use neurojam24_core::{
+    ClientMessage, Difficulty, GameStatus, Profile, ProtocolError, RejoinToken, Replay, RequestId,
+    RoomConfig, RoomId, ServerMessage, CHAT_BURST, CHAT_RATE, HEARTBEAT_INTERVAL,
+    HEARTBEAT_TIMEOUT, LEADERBOARD_SIZE, MAX_MESSAGE_SIZE,
+};
+use rustls::ServerConfig;
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    flag,
+};
+use tracing::{error, info, info_span, warn};
+use tungstenite::{
+    accept_with_config,
+    protocol::{frame::coding::CloseCode, CloseFrame, WebSocketConfig},
+    Message, WebSocket,
+};
+
+use limit::RateLimiter;
+use metrics::Metrics;
+use profiles::Profiles;
+use queue::{Queue, QueueEntry};
+use room::Room;
+use tls::Stream;
+
+mod clock;
+mod console;
+mod limit;
+mod logging;
+mod metrics;
+mod profiles;
+mod queue;
+mod room;
+mod status;
+mod tls;
+
+type ConnectionId = u32;
+
+// A websocket connection, which takes a seat once it has been matched
+struct Connection {
+    addr: SocketAddr,
+    seat: Option<(RoomId, u8)>,
+    name: Option<String>, // Given when looking for a match
+    connected_at: Instant,
+    outbox: Vec<ServerMessage>,
+    kicked: bool,
+}
 
 pub struct Server {
-    game_status: GameStatus,
-    inputs: [Option<PlayerAction>; 2],
-    needs_send: [bool; 2],
-    needs_step: bool,
-    player: [bool; 2],
-    result: Option<GameResult>,
-    message: Option<Message>,
+    config: RoomConfig, // For matched players without a ruleset preference
+    connections: HashMap<ConnectionId, Connection>,
+    next_connection: ConnectionId,
+    rooms: BTreeMap<RoomId, Room>,
+    next_room: RoomId,
+    queue: Queue,
+    profiles: Profiles,
+    shutting_down: bool,
+    metrics: Metrics,
 }
 impl Server {
-    fn new() -> Self {
-        let game_status = GameStatus::initial();
-        let inputs = [None; 2];
-        let needs_send = [true; 2];
-        let needs_step = false;
-        let player = [false; 2];
-        let result = None;
-        let message = None;
+    fn new(config: RoomConfig, profiles: Profiles) -> Self {
         Self {
-            game_status,
-            inputs,
-            needs_step,
-            needs_send,
-            player,
-            result,
-            message,
+            config,
+            connections: HashMap::new(),
+            next_connection: 0,
+            rooms: BTreeMap::new(),
+            next_room: 0,
+            queue: Queue::default(),
+            profiles,
+            shutting_down: false,
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn connect(&mut self, addr: SocketAddr) -> ConnectionId {
+        let connection_id = self.next_connection;
+        self.next_connection += 1;
+        let connection = Connection {
+            addr,
+            seat: None,
+            name: None,
+            connected_at: Instant::now(),
+            outbox: Vec::new(),
+            kicked: false,
+        };
+        self.connections.insert(connection_id, connection);
+        self.metrics.connections += 1;
+        connection_id
+    }
+
+    fn disconnect(&mut self, connection_id: ConnectionId) {
+        if self.queue.remove(connection_id) {
+            self.send_queue_positions();
+        }
+        if let Some((room_id, player_id)) = self.connections[&connection_id].seat {
+            self.leave(connection_id, room_id, player_id, true);
+        }
+        self.connections.remove(&connection_id);
+    }
+
+    fn receive(
+        &mut self,
+        connection_id: ConnectionId,
+        message: ClientMessage,
+    ) -> Result<(), ProtocolError> {
+        let seat = self.connections[&connection_id].seat;
+        match message {
+            ClientMessage::FindMatch(request_id, name, preferences) => {
+                if seat.is_some() || self.queue.contains(connection_id) {
+                    return Err(ProtocolError::AlreadyJoined);
+                }
+                let name = Profile::valid_name(&name).ok_or(ProtocolError::InvalidName)?;
+                let taken = self.connections.iter().any(|(&id, connection)| {
+                    id != connection_id && connection.name.as_deref() == Some(name)
+                });
+                if taken {
+                    return Err(ProtocolError::NameTaken);
+                }
+                // Players without a preference play by the server's rules, not their opponent's
+                let ruleset = match preferences.ruleset {
+                    Some(ruleset) => ruleset.validate().ok_or(ProtocolError::InvalidRuleset)?,
+                    None => self.config,
+                };
+                info!(name, ?preferences, "Looking for a match");
+                let connection = self.connections.get_mut(&connection_id);
+                connection
+                    .expect("Connections are registered before receiving")
+                    .name = Some(name.to_string());
+                if let Some(difficulty) = preferences.bot {
+                    self.play_bot(connection_id, request_id, ruleset, difficulty);
+                    return Ok(());
+                }
+                let rating = self.profiles.get(name).rating;
+                let entry =
+                    QueueEntry::new(connection_id, request_id, preferences, ruleset, rating);
+                self.queue.push(entry);
+                self.matchmake();
+            }
+            ClientMessage::Rejoin(request_id, room_id, token) => {
+                if seat.is_some() || self.queue.contains(connection_id) {
+                    return Err(ProtocolError::AlreadyJoined);
+                }
+                self.rejoin(connection_id, request_id, room_id, token)?;
+            }
+            ClientMessage::CancelMatch => {
+                if !self.queue.remove(connection_id) {
+                    return Err(ProtocolError::NotQueued);
+                }
+                info!("Stopped looking for a match");
+                self.push(connection_id, ServerMessage::MatchCancelled);
+                self.send_queue_positions();
+            }
+            ClientMessage::Leaderboard(request_id) => {
+                let mut leaderboard = self.profiles.leaderboard();
+                leaderboard.truncate(LEADERBOARD_SIZE);
+                let message = ServerMessage::Leaderboard(request_id, leaderboard);
+                self.push(connection_id, message);
+            }
+            ClientMessage::Leave => {
+                let (room_id, player_id) = seat.ok_or(ProtocolError::NotJoined)?;
+                self.leave(connection_id, room_id, player_id, false);
+            }
+            ClientMessage::Chat(chat) => {
+                let (room_id, player_id) = seat.ok_or(ProtocolError::NotJoined)?;
+                let chat = chat.validate().ok_or(ProtocolError::InvalidChat)?;
+                self.room(room_id).chat(player_id, chat);
+                self.flush();
+            }
+            message => {
+                let (room_id, player_id) = seat.ok_or(ProtocolError::NotJoined)?;
+                self.room(room_id).receive(player_id, message);
+                self.flush();
+            }
+        }
+        Ok(())
+    }
+
+    // Pairs up everyone in the queue who can be, each pair in a new room
+    fn matchmake(&mut self) {
+        while let Some((first, second)) = self.queue.pop_match() {
+            let room_id = self.next_room;
+            self.next_room += 1;
+            let rated = first.preferences.rated;
+            let mut room = Room::new(room_id, first.ruleset, rated);
+            info!(
+                room_id,
+                rated,
+                connections = ?[first.connection_id, second.connection_id],
+                waited = ?first.queued_at.elapsed(),
+                "Match found"
+            );
+            for entry in [first, second] {
+                let name = self.connections[&entry.connection_id].name.as_deref();
+                let profile = self.profiles.get(name.expect("Named before queueing"));
+                let player_id = room
+                    .join(entry.connection_id, entry.request_id, profile)
+                    .expect("New rooms have two free seats");
+                if let Some(connection) = self.connections.get_mut(&entry.connection_id) {
+                    connection.seat = Some((room_id, player_id));
+                }
+            }
+            self.rooms.insert(room_id, room);
+        }
+        self.flush();
+        self.send_queue_positions();
+    }
+
+    // Seats the player in a new room opposite a bot, without queueing
+    fn play_bot(
+        &mut self,
+        connection_id: ConnectionId,
+        request_id: RequestId,
+        ruleset: RoomConfig,
+        difficulty: Difficulty,
+    ) {
+        let room_id = self.next_room;
+        self.next_room += 1;
+        let mut room = Room::new(room_id, ruleset, false);
+        info!(room_id, %difficulty, "Bot game started");
+        let name = self.connections[&connection_id].name.as_deref();
+        let profile = self.profiles.get(name.expect("Named before playing"));
+        let player_id = room
+            .join(connection_id, request_id, profile)
+            .expect("New rooms have two free seats");
+        room.add_bot(difficulty)
+            .expect("New rooms have two free seats");
+        if let Some(connection) = self.connections.get_mut(&connection_id) {
+            connection.seat = Some((room_id, player_id));
+        }
+        self.rooms.insert(room_id, room);
+        self.flush();
+    }
+
+    // Puts the player back in the seat held for them since they lost their connection, if any
+    // A connection the seat is taken from is closed, as the player has moved on from it
+    fn rejoin(
+        &mut self,
+        connection_id: ConnectionId,
+        request_id: RequestId,
+        room_id: RoomId,
+        token: RejoinToken,
+    ) -> Result<(), ProtocolError> {
+        let room = self.rooms.get_mut(&room_id);
+        let room = room.ok_or(ProtocolError::NoSeatHeld)?;
+        let (player_id, replaced) = room
+            .rejoin(connection_id, request_id, token)
+            .ok_or(ProtocolError::NoSeatHeld)?;
+        let name = room.profiles[player_id as usize]
+            .as_ref()
+            .map(|profile| profile.name.clone());
+        if let Some(connection) = replaced.and_then(|id| self.connections.get_mut(&id)) {
+            connection.seat = None;
+            connection.kicked = true;
+        }
+        let connection = self.connections.get_mut(&connection_id);
+        let connection = connection.expect("Connections are registered before receiving");
+        connection.seat = Some((room_id, player_id));
+        connection.name = name;
+        self.flush();
+        Ok(())
+    }
+
+    // `dropped` holds the seat of a player whose connection was lost in the middle of a game
+    fn leave(
+        &mut self,
+        connection_id: ConnectionId,
+        room_id: RoomId,
+        player_id: u8,
+        dropped: bool,
+    ) {
+        if let Some(connection) = self.connections.get_mut(&connection_id) {
+            connection.seat = None;
+        }
+        self.room(room_id).leave(player_id, dropped);
+        self.flush();
+        self.close_abandoned();
+    }
+
+    fn close_abandoned(&mut self) {
+        let shutting_down = self.shutting_down;
+        self.rooms.retain(|&room_id, room| {
+            if !room.is_abandoned() {
+                return true;
+            }
+            // Unfinished games were all saved once shutting down started
+            if !shutting_down {
+                room.save_unfinished();
+            }
+            info!(room_id, "Room closed");
+            false
+        });
+    }
+
+    fn room(&mut self, room_id: RoomId) -> &mut Room {
+        self.rooms
+            .get_mut(&room_id)
+            .expect("Rooms close once their seats are empty")
+    }
+
+    fn tick(&mut self) {
+        for room in self.rooms.values_mut() {
+            room.tick();
         }
+        self.flush();
+        // Rooms whose dropped players didn't come back in time
+        self.close_abandoned();
     }
 
-    pub fn set_input(&mut self, player_id: u8, action: PlayerAction) {
-        self.inputs[player_id as usize] = Some(action);
+    // Records finished games, delivers what rooms sent to their players, and adds up their metrics
+    fn flush(&mut self) {
+        for room in self.rooms.values_mut() {
+            for result in std::mem::take(&mut room.results) {
+                if room.bots.iter().any(Option::is_some) {
+                    continue; // Practice against bots doesn't count
+                }
+                let [Some(first), Some(second)] = &room.profiles else {
+                    continue;
+                };
+                let names = [first.name.as_str(), second.name.as_str()];
+                let profiles = self.profiles.record(names, result, room.rated);
+                room.set_profiles(profiles);
+            }
+            for (player_id, message) in room.outbox.drain(..) {
+                let connection = room.seats[player_id as usize]
+                    .and_then(|connection_id| self.connections.get_mut(&connection_id));
+                if let Some(connection) = connection {
+                    connection.outbox.push(message);
+                }
+            }
+            self.metrics.merge(std::mem::take(&mut room.metrics));
+        }
     }
+
+    fn send_queue_positions(&mut self) {
+        for (connection_id, position) in self.queue.position_updates() {
+            self.push(connection_id, ServerMessage::QueuePosition(position));
+        }
+    }
+
+    fn push(&mut self, connection_id: ConnectionId, message: ServerMessage) {
+        if let Some(connection) = self.connections.get_mut(&connection_id) {
+            connection.outbox.push(message);
+        }
+    }
+
+    // Reaches every connection, in a room or not
+    fn announce(&mut self, text: &str) {
+        for connection in self.connections.values_mut() {
+            connection
+                .outbox
+                .push(ServerMessage::Announcement(text.to_string()));
+        }
+    }
+
+    // Keeps unfinished games and tells every connection the server is going away. Connection
+    // threads close once their outbox is drained
+    fn shutdown(&mut self) {
+        if self.shutting_down {
+            return;
+        }
+        self.shutting_down = true;
+        for room in self.rooms.values() {
+            room.save_unfinished();
+        }
+        for connection in self.connections.values_mut() {
+            connection.outbox.push(ServerMessage::ServerShutdown);
+        }
+    }
+
+    fn kick(&mut self, connection_id: ConnectionId) -> bool {
+        match self.connections.get_mut(&connection_id) {
+            Some(connection) => {
+                connection.kicked = true;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
+}
+
+fn timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn save_replay(room_id: RoomId, replay: &Replay) -> std::io::Result<()> {
+    let name = format!("replays/{}-{}", timestamp(), room_id);
+    if cfg!(test) {
+        return Ok(()); // Tests don't leave replays behind
+    }
+    std::fs::create_dir_all("replays")?;
+    std::fs::write(format!("{}.json", name), replay.ser())?;
+    std::fs::write(format!("{}.txt", name), replay.notation())
+}
+
+// The replay so far, with the current position after the notation
+fn save_snapshot(
+    room_id: RoomId,
+    replay: &Replay,
+    game_status: &GameStatus,
+) -> std::io::Result<()> {
+    let name = format!("replays/{}-{}-unfinished", timestamp(), room_id);
+    if cfg!(test) {
+        return Ok(()); // Tests don't leave replays behind
+    }
+    std::fs::create_dir_all("replays")?;
+    std::fs::write(format!("{}.json", name), replay.ser())?;
+    std::fs::write(
+        format!("{}.txt", name),
+        format!("{}\n{}", replay.notation(), game_status),
+    )
 }
 
 const TICK_LENGTH: std::time::Duration = std::time::Duration::from_millis(5);
+const MESSAGE_RATE: f32 = 10.0; // Messages per second a connection can sustain
+const MESSAGE_BURST: f32 = 20.0;
+const MALFORMED_RATE: f32 = 0.1; // Malformed messages per second a connection is forgiven
+const MALFORMED_BURST: f32 = 10.0; // Before the connection is closed
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(500);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5); // For the TLS and websocket handshakes
 
-fn main() {
-    let game_server = Arc::new(Mutex::new(Server::new()));
+fn close(socket: &mut WebSocket<Stream>, code: CloseCode, reason: &str) {
+    let _ = socket.close(Some(CloseFrame {
+        code,
+        reason: reason.into(),
+    }));
+    let _ = socket.flush();
+}
 
-    let server_ref = game_server.clone();
-    spawn(move || loop {
-        let frame_time = std::time::Instant::now();
+fn serve_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    tls: Option<Arc<ServerConfig>>,
+    server_ref: Arc<Mutex<Server>>,
+) {
+    // Handshakes block, so a peer that never sends anything mustn't hold the thread forever
+    let configured = stream
+        .set_nonblocking(false)
+        .and_then(|()| stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)));
+    if let Err(error) = configured {
+        warn!(%error, "Failed to configure connection");
+        return;
+    }
+    let stream = match Stream::new(stream, tls) {
+        Ok(stream) => stream,
+        Err(error) => {
+            warn!(%error, "Failed to set up TLS");
+            return;
+        }
+    };
+    let config = WebSocketConfig::default()
+        .max_message_size(Some(MAX_MESSAGE_SIZE))
+        .max_frame_size(Some(MAX_MESSAGE_SIZE));
+    let Ok(mut socket) = accept_with_config(stream, Some(config)) else {
+        warn!("Websocket handshake failed");
+        return;
+    };
+    let tcp = socket.get_ref().tcp();
+    if let Err(error) = tcp
+        .set_read_timeout(None)
+        .and_then(|()| tcp.set_nonblocking(true))
+    {
+        warn!(%error, "Failed to configure connection");
+        return;
+    }
 
-        let mut game_server = server_ref.lock().unwrap();
-        if game_server.needs_step {
-            if game_server.inputs.iter().all(|i| i.is_some()) {
-                let inputs = game_server.inputs.map(|i| i.expect("verified"));
-                let res = game_server.game_status.push_actions(inputs);
-                match res {
-                    Ok(game_result) => {
-                        game_server.result = game_result;
-                        for i in 0..2 {
-                            game_server.needs_send[i] = true;
-                            game_server.inputs[i] = None;
-                        }
-                    }
-                    Err(error) => match error {
-                        neurojam24_core::Error::InvalidMove(reason) => {
-                            println!("Invalid move (Reason: {})", reason);
-                            game_server.inputs = [None; 2];
-                            game_server.message =
-                                Some(Message::Text(NetBlob::InvalidMove.ser().into()));
-                        }
-                    },
+    let connection_id = server_ref.lock().unwrap().connect(addr);
+    let _span = info_span!("connection", id = connection_id, %addr).entered();
+    info!("Connection opened");
+    let mut frame_time;
+    let mut last_seen = Instant::now();
+    let mut last_ping = Instant::now();
+    let mut limiter = RateLimiter::new(MESSAGE_BURST, MESSAGE_RATE);
+    let mut chat_limiter = RateLimiter::new(CHAT_BURST, CHAT_RATE);
+    // Only messages that can't be understood count against the connection, since the others
+    // can come from a well-behaved client racing the server, e.g. cancelling a found match
+    let mut malformed_limiter = RateLimiter::new(MALFORMED_BURST, MALFORMED_RATE);
+
+    'connection: loop {
+        frame_time = std::time::Instant::now();
+        loop {
+            let msg = match socket.read() {
+                Ok(msg) => msg,
+                Err(tungstenite::Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => {
+                    break;
+                }
+                Err(tungstenite::Error::Capacity(_)) => {
+                    // There's no resynchronising after an oversized message
+                    let error = ServerMessage::ProtocolError(None, ProtocolError::TooLarge);
+                    let _ = socket.send(Message::Text(error.ser().into()));
+                    close(&mut socket, CloseCode::Size, "Message too large");
+                    break 'connection;
+                }
+                Err(_) => break 'connection,
+            };
+            // Any frame, including pongs, shows the connection is alive
+            last_seen = Instant::now();
+            let message = match msg {
+                Message::Text(text) => ClientMessage::deser(text.as_str()).ok(),
+                Message::Binary(_) => None,
+                Message::Close(_) => break 'connection,
+                _ => continue,
+            };
+            let request_id = message.as_ref().and_then(ClientMessage::request_id);
+            let result = match message {
+                _ if !limiter.try_take() => Err(ProtocolError::RateLimited),
+                // Dropped quietly, since clients throttle chat themselves and any that gets
+                // through is harmless
+                Some(ClientMessage::Chat(_)) if !chat_limiter.try_take() => Ok(()),
+                Some(message) => server_ref.lock().unwrap().receive(connection_id, message),
+                None => Err(ProtocolError::Malformed),
+            };
+            if let Err(error) = result {
+                warn!(%error, "Protocol error");
+                let malformed = matches!(error, ProtocolError::Malformed | ProtocolError::TooLarge);
+                let message = ServerMessage::ProtocolError(request_id, error);
+                let _ = socket.send(Message::Text(message.ser().into()));
+                if malformed && !malformed_limiter.try_take() {
+                    warn!("Too many malformed messages, closing connection");
+                    close(
+                        &mut socket,
+                        CloseCode::Policy,
+                        "Too many malformed messages",
+                    );
+                    break 'connection;
                 }
             }
-            game_server.needs_step = false;
         }
+        if last_seen.elapsed() >= HEARTBEAT_TIMEOUT {
+            info!("Connection timed out");
+            break;
+        }
+        if last_ping.elapsed() >= HEARTBEAT_INTERVAL {
+            last_ping = Instant::now();
+            let _ = socket.send(Message::Ping(Default::default()));
+        }
+        let mut game_server = server_ref.lock().unwrap();
+        let shutting_down = game_server.shutting_down;
+        let connection = game_server
+            .connections
+            .get_mut(&connection_id)
+            .expect("Only this thread removes the connection");
+        for message in connection.outbox.drain(..) {
+            let _ = socket.send(Message::Text(message.ser().into()));
+        }
+        let kicked = connection.kicked;
+        drop(game_server);
+        if kicked {
+            info!("Kicked by the server operator");
+            close(
+                &mut socket,
+                CloseCode::Policy,
+                "Kicked by the server operator",
+            );
+            break;
+        }
+        if shutting_down {
+            close(&mut socket, CloseCode::Away, "The server is shutting down");
+            break;
+        }
+        if frame_time.elapsed() < TICK_LENGTH {
+            std::thread::sleep(TICK_LENGTH - frame_time.elapsed());
+        }
+    }
+    server_ref.lock().unwrap().disconnect(connection_id);
+}
+
+fn main() -> ExitCode {
+    let config = RoomConfig {
+        commit_reveal: std::env::args().any(|arg| arg == "--commit-reveal"),
+        turn_time: arg_value("--turn-time").map(|secs| secs.parse().expect("Invalid turn time")),
+        timeout_action: arg_value("--timeout-action")
+            .map(|action| action.parse().expect("Invalid timeout action"))
+            .unwrap_or_default(),
+        time_bank: arg_value("--time-bank").map(|secs| secs.parse().expect("Invalid time bank")),
+        increment: arg_value("--increment")
+            .map(|secs| secs.parse().expect("Invalid increment"))
+            .unwrap_or_default(),
+        swap_spawns: std::env::args().any(|arg| arg == "--swap-spawns"),
+        best_of: arg_value("--best-of").map(|games| games.parse().expect("Invalid series length")),
+    };
+    let rotation = arg_value("--log-rotation").unwrap_or("daily".to_string());
+    let _log_guard = logging::init(
+        std::env::args().any(|arg| arg == "--log-json"),
+        arg_value("--log-dir").as_deref(),
+        &rotation,
+    );
+    let profiles_path = arg_value("--profiles").unwrap_or("profiles.json".to_string());
+    let profiles = match Profiles::load(profiles_path.into()) {
+        Ok(profiles) => profiles,
+        Err(error) => {
+            error!(%error, "Failed to load profiles");
+            return ExitCode::FAILURE;
+        }
+    };
+    let game_server = Arc::new(Mutex::new(Server::new(config, profiles)));
+
+    // A second signal exits immediately, in case shutting down hangs
+    let signalled = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        let registered = flag::register_conditional_shutdown(signal, 1, signalled.clone())
+            .and_then(|_| flag::register(signal, signalled.clone()));
+        if let Err(error) = registered {
+            error!(%error, "Failed to install signal handler");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let server_ref = game_server.clone();
+    let step = spawn(move || loop {
+        let frame_time = std::time::Instant::now();
+
+        let mut server = server_ref.lock().unwrap();
+        if server.shutting_down {
+            break;
+        }
+        server.tick();
+        drop(server);
 
         if frame_time.elapsed() < 10 * TICK_LENGTH {
             std::thread::sleep(10 * TICK_LENGTH - frame_time.elapsed());
         }
     });
-    let server = TcpListener::bind("0.0.0.0:4444").unwrap();
-    server.set_nonblocking(true);
-    for stream in server.incoming() {
-        if stream.is_ok() {
-            let server_ref = game_server.clone();
-            spawn(move || {
-                let mut socket = accept(stream.unwrap()).unwrap();
-
-                let mut player_id = None;
-                let mut frame_time;
-
-                loop {
-                    frame_time = std::time::Instant::now();
-                    while let Ok(msg) = socket.read() {
-                        // dbg!(&msg);
-                        if msg.is_text() {
-                            match NetBlob::deser(msg.into_text().expect("It should be").as_str()) {
-                                Ok(blob) => match blob {
-                                    NetBlob::Join => {
-                                        println!("Join requested");
-                                        let mut game_server = server_ref.lock().unwrap();
-                                        if let Some(id) = (0..2).find(|&i| !game_server.player[i]) {
-                                            if !game_server.player[id as usize] {
-                                                player_id = Some(id as u8);
-                                                game_server.player[id as usize] = true;
-                                                println!("Assigned id {}", id);
-                                                socket.send(Message::Text(
-                                                    NetBlob::Assign(id as u8).ser().into(),
-                                                ));
-                                            }
-                                        }
-                                    }
-                                    NetBlob::Assign(_) => todo!(),
-                                    NetBlob::Action(action) => {
-                                        if let Some(player_id) = player_id {
-                                            println!("Received move for player {}", player_id);
-                                            let mut game_server = server_ref.lock().unwrap();
-                                            game_server.set_input(player_id, action);
-                                            game_server.needs_step = true;
-                                        }
-                                    }
-                                    NetBlob::Leave => {
-                                        if let Some(player_id) = &mut player_id {
-                                            println!("Player {} left", player_id);
-                                            server_ref.lock().unwrap().player
-                                                [*player_id as usize] = false;
-                                        }
-                                        player_id = None
-                                    }
-                                    NetBlob::Display(_) => todo!(),
-                                    NetBlob::Stati(_) => todo!(),
-                                    NetBlob::Result(_) => todo!(),
-                                    NetBlob::Start => todo!(),
-                                    NetBlob::InvalidMove => todo!(),
-                                },
-                                Err(_) => {
-                                    dbg!("Bad message");
-                                }
-                            };
-                        } else if msg.is_close() {
-                            break;
-                        }
-                    }
-                    let mut game_server = server_ref.lock().unwrap();
-                    if let Some(player_id) = player_id {
-                        if game_server.needs_send[player_id as usize] {
-                            socket.send(Message::Text(
-                                NetBlob::Display(game_server.game_status.display())
-                                    .ser()
-                                    .into(),
-                            ));
-                            socket.send(Message::Text(
-                                NetBlob::Stati(game_server.game_status.player_stati)
-                                    .ser()
-                                    .into(),
-                            ));
-                            if let Some(result) = game_server.result {
-                                match result {
-                                    GameResult::Win(player_id) => {
-                                        println!("Player {} wins", player_id)
-                                    }
-                                    GameResult::Draw => println!("Draw"),
-                                }
-                                socket.send(Message::Text(NetBlob::Result(result).ser().into()));
-                            }
-                            socket.send(Message::Text(NetBlob::Start.ser().into()));
-                            game_server.needs_send[player_id as usize] = false;
-                        }
-                        if let Some(message) = &game_server.message {
-                            socket.send(message.clone());
-                        }
-                    }
-                    drop(game_server);
-                    if frame_time.elapsed() < TICK_LENGTH {
-                        std::thread::sleep(TICK_LENGTH - frame_time.elapsed());
-                    }
+    let server_ref = game_server.clone();
+    spawn(move || console::run(server_ref));
+
+    let status = match arg_value("--status-port") {
+        Some(port) => {
+            let port: u16 = port.parse().expect("Invalid status port");
+            // Only reachable locally, since it isn't authenticated
+            let listener = match TcpListener::bind(("127.0.0.1", port)) {
+                Ok(listener) => listener,
+                Err(error) => {
+                    error!(%error, "Failed to bind the status port");
+                    return ExitCode::FAILURE;
                 }
-            });
-        } else {
-            std::thread::sleep(std::time::Duration::from_millis(500));
+            };
+            info!("Status endpoint listening on http://127.0.0.1:{}", port);
+            let server_ref = game_server.clone();
+            Some(spawn(move || status::serve(listener, server_ref)))
+        }
+        None => None,
+    };
+
+    let tls = match (arg_value("--tls-cert"), arg_value("--tls-key")) {
+        (Some(cert), Some(key)) => match tls::load_config(&cert, &key) {
+            Ok(config) => Some(config),
+            Err(error) => {
+                error!(%error, "Failed to load the TLS certificate");
+                return ExitCode::FAILURE;
+            }
+        },
+        (None, None) => None,
+        _ => {
+            error!("--tls-cert and --tls-key have to be given together");
+            return ExitCode::FAILURE;
+        }
+    };
+    let listener = match TcpListener::bind("0.0.0.0:4444") {
+        Ok(listener) => listener,
+        Err(error) => {
+            error!(%error, "Failed to bind the game port");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(error) = listener.set_nonblocking(true) {
+        error!(%error, "Failed to configure the game port");
+        return ExitCode::FAILURE;
+    }
+    match tls {
+        Some(_) => info!("Listening on wss://0.0.0.0:4444"),
+        None => info!("Listening on ws://0.0.0.0:4444"),
+    }
+    let mut connections = Vec::new();
+    loop {
+        {
+            let mut server = game_server.lock().unwrap();
+            if signalled.load(Ordering::Relaxed) {
+                server.shutdown();
+            }
+            if server.shutting_down {
+                break;
+            }
+        }
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                let server_ref = game_server.clone();
+                let tls = tls.clone();
+                connections.push(spawn(move || {
+                    serve_connection(stream, addr, tls, server_ref)
+                }));
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                std::thread::sleep(ACCEPT_INTERVAL);
+            }
+            Err(error) => {
+                warn!(%error, "Failed to accept connection");
+                std::thread::sleep(ACCEPT_INTERVAL);
+            }
+        }
+        connections.retain(|connection| !connection.is_finished());
+    }
+    drop(listener);
+    info!("Shutting down");
+    // The console thread is left behind, since it blocks on stdin
+    let threads = connections.into_iter().chain([step]).chain(status);
+    for thread in threads {
+        if thread.join().is_err() {
+            error!("A thread panicked while shutting down");
+        }
+    }
+    info!("Server shut down");
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use neurojam24_core::{ChatMessage, Difficulty, MatchPreferences, RoomPhase};
+
+    use super::*;
+
+    fn addr() -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], 4444))
+    }
+
+    // Profiles aren't written unless a game finishes
+    fn server() -> Server {
+        let profiles = Profiles::load("unused-profiles.json".into()).unwrap();
+        Server::new(RoomConfig::default(), profiles)
+    }
+
+    fn find_match(server: &mut Server, connection_id: ConnectionId) -> Result<(), ProtocolError> {
+        let name = format!("player{}", connection_id);
+        let message = ClientMessage::FindMatch(0, name, MatchPreferences::default());
+        server.receive(connection_id, message)
+    }
+
+    // From the latest seat the connection was given
+    fn token(server: &Server, connection_id: ConnectionId) -> RejoinToken {
+        let outbox = &server.connections[&connection_id].outbox;
+        let token = outbox.iter().rev().find_map(|message| match message {
+            ServerMessage::Assign(_, _, _, token) => Some(*token),
+            _ => None,
+        });
+        token.expect("The connection was given a seat")
+    }
+
+    #[test]
+    fn moves_need_a_seat() {
+        let mut server = server();
+        let connection_id = server.connect(addr());
+        assert_eq!(
+            server.receive(connection_id, ClientMessage::Ready),
+            Err(ProtocolError::NotJoined)
+        );
+        assert_eq!(
+            server.receive(connection_id, ClientMessage::Leave),
+            Err(ProtocolError::NotJoined)
+        );
+    }
+
+    #[test]
+    fn queued_players_are_paired_into_a_new_room() {
+        let mut server = server();
+        let connections: Vec<_> = (0..3).map(|_| server.connect(addr())).collect();
+        for &connection_id in &connections {
+            assert_eq!(find_match(&mut server, connection_id), Ok(()));
+        }
+        let seat = |server: &Server, index: usize| server.connections[&connections[index]].seat;
+        assert_eq!(seat(&server, 0), Some((0, 0)));
+        assert_eq!(seat(&server, 1), Some((0, 1)));
+        // The odd one out keeps waiting
+        assert_eq!(seat(&server, 2), None);
+        assert!(server.queue.contains(connections[2]));
+        for connection_id in [connections[0], connections[2]] {
+            assert_eq!(
+                find_match(&mut server, connection_id),
+                Err(ProtocolError::AlreadyJoined)
+            );
+        }
+    }
+
+    #[test]
+    fn matches_can_be_cancelled_while_queued() {
+        let mut server = server();
+        let connection_id = server.connect(addr());
+        assert_eq!(
+            server.receive(connection_id, ClientMessage::CancelMatch),
+            Err(ProtocolError::NotQueued)
+        );
+        find_match(&mut server, connection_id).unwrap();
+        assert_eq!(
+            server.receive(connection_id, ClientMessage::CancelMatch),
+            Ok(())
+        );
+        assert!(!server.queue.contains(connection_id));
+        let outbox = &server.connections[&connection_id].outbox;
+        assert!(matches!(outbox.last(), Some(ServerMessage::MatchCancelled)));
+    }
+
+    #[test]
+    fn rooms_close_once_both_players_leave() {
+        let mut server = server();
+        let connections: Vec<_> = (0..2).map(|_| server.connect(addr())).collect();
+        for &connection_id in &connections {
+            find_match(&mut server, connection_id).unwrap();
+        }
+        assert_eq!(server.receive(connections[0], ClientMessage::Leave), Ok(()));
+        assert_eq!(server.connections[&connections[0]].seat, None);
+        assert!(server.rooms.contains_key(&0));
+        server.disconnect(connections[1]);
+        assert!(server.rooms.is_empty());
+    }
+
+    #[test]
+    fn announcements_reach_connections_without_a_seat() {
+        let mut server = server();
+        let connections: Vec<_> = (0..3).map(|_| server.connect(addr())).collect();
+        for &connection_id in &connections[..2] {
+            find_match(&mut server, connection_id).unwrap();
+        }
+        for connection in server.connections.values_mut() {
+            connection.outbox.clear();
+        }
+        server.announce("Restarting soon");
+        for connection_id in connections {
+            let outbox = &server.connections[&connection_id].outbox;
+            assert!(matches!(
+                &outbox[..],
+                [ServerMessage::Announcement(text)] if text == "Restarting soon"
+            ));
+        }
+    }
+
+    #[test]
+    fn kicks_need_a_connection() {
+        let mut server = server();
+        let connection_id = server.connect(addr());
+        assert!(!server.kick(connection_id + 1));
+        assert!(server.kick(connection_id));
+        assert!(server.connections[&connection_id].kicked);
+    }
+
+    #[test]
+    fn names_are_checked_before_queueing() {
+        let mut server = server();
+        let connections: Vec<_> = (0..2).map(|_| server.connect(addr())).collect();
+        let preferences = MatchPreferences::default();
+        for name in ["", "   ", "bad\nname"] {
+            let message = ClientMessage::FindMatch(0, name.to_string(), preferences);
+            assert_eq!(
+                server.receive(connections[0], message),
+                Err(ProtocolError::InvalidName)
+            );
+        }
+        for (connection_id, expected) in connections
+            .into_iter()
+            .zip([Ok(()), Err(ProtocolError::NameTaken)])
+        {
+            let message = ClientMessage::FindMatch(0, " alice ".to_string(), preferences);
+            assert_eq!(server.receive(connection_id, message), expected);
+        }
+        assert!(server
+            .connections
+            .values()
+            .any(|connection| connection.name.as_deref() == Some("alice")));
+    }
+
+    #[test]
+    fn chat_reaches_the_whole_room() {
+        let mut server = server();
+        let connections: Vec<_> = (0..3).map(|_| server.connect(addr())).collect();
+        for &connection_id in &connections[..2] {
+            find_match(&mut server, connection_id).unwrap();
+        }
+        let chat = |text: &str| ClientMessage::Chat(ChatMessage::Text(text.to_string()));
+        assert_eq!(server.receive(connections[0], chat(" gg ")), Ok(()));
+        for &connection_id in &connections[..2] {
+            let outbox = &server.connections[&connection_id].outbox;
+            assert!(matches!(
+                outbox.last(),
+                Some(ServerMessage::Chat(0, ChatMessage::Text(text))) if text == "gg"
+            ));
+        }
+        assert_eq!(
+            server.receive(connections[0], chat("")),
+            Err(ProtocolError::InvalidChat)
+        );
+        assert_eq!(
+            server.receive(connections[2], chat("hello?")),
+            Err(ProtocolError::NotJoined)
+        );
+    }
+
+    #[test]
+    fn bot_games_skip_the_queue() {
+        let mut server = server();
+        let connection_id = server.connect(addr());
+        let preferences = MatchPreferences {
+            bot: Some(Difficulty::Medium),
+            ..Default::default()
+        };
+        let message = ClientMessage::FindMatch(0, "alice".to_string(), preferences);
+        assert_eq!(server.receive(connection_id, message), Ok(()));
+        assert_eq!(server.connections[&connection_id].seat, Some((0, 0)));
+        assert!(!server.queue.contains(connection_id));
+        assert_eq!(server.rooms[&0].bots, [None, Some(Difficulty::Medium)]);
+    }
+
+    #[test]
+    fn rulesets_out_of_bounds_are_refused() {
+        let mut server = server();
+        let connection_id = server.connect(addr());
+        let preferences = MatchPreferences {
+            ruleset: Some(RoomConfig {
+                turn_time: Some(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let message = ClientMessage::FindMatch(0, "alice".to_string(), preferences);
+        assert_eq!(
+            server.receive(connection_id, message),
+            Err(ProtocolError::InvalidRuleset)
+        );
+        assert!(!server.queue.contains(connection_id));
+        assert_eq!(server.connections[&connection_id].name, None);
+    }
+
+    #[test]
+    fn dropped_players_rejoin_with_their_token() {
+        let mut server = server();
+        let connections: Vec<_> = (0..2).map(|_| server.connect(addr())).collect();
+        for &connection_id in &connections {
+            find_match(&mut server, connection_id).unwrap();
+        }
+        let token = token(&server, connections[0]);
+        server.room(0).phase = RoomPhase::InProgress;
+        server.disconnect(connections[0]);
+        assert!(server.rooms.contains_key(&0));
+        // The name alone doesn't get the seat back
+        let connection_id = server.connect(addr());
+        assert_eq!(find_match(&mut server, connection_id), Ok(()));
+        assert_eq!(server.connections[&connection_id].seat, None);
+        let connection_id = server.connect(addr());
+        let rejoin = |token| ClientMessage::Rejoin(0, 0, token);
+        assert_eq!(
+            server.receive(connection_id, rejoin(token.wrapping_add(1))),
+            Err(ProtocolError::NoSeatHeld)
+        );
+        assert_eq!(server.receive(connection_id, rejoin(token)), Ok(()));
+        assert_eq!(server.connections[&connection_id].seat, Some((0, 0)));
+        let name = server.connections[&connection_id].name.as_deref();
+        assert_eq!(name, Some("player0"));
+    }
+
+    #[test]
+    fn rejoining_closes_the_stale_connection() {
+        let mut server = server();
+        let connections: Vec<_> = (0..2).map(|_| server.connect(addr())).collect();
+        for &connection_id in &connections {
+            find_match(&mut server, connection_id).unwrap();
         }
+        let connection_id = server.connect(addr());
+        let message = ClientMessage::Rejoin(0, 0, token(&server, connections[1]));
+        assert_eq!(server.receive(connection_id, message), Ok(()));
+        assert_eq!(server.connections[&connection_id].seat, Some((0, 1)));
+        assert_eq!(server.connections[&connections[1]].seat, None);
+        assert!(server.connections[&connections[1]].kicked);
     }
 }
@@ -0,0 +1,118 @@
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+use neurojam24_core::InvalidMove;
+
+// Counters since the server started, exported in Prometheus' text format
+#[derive(Default)]
+pub struct Metrics {
+    pub connections: u64,
+    pub turns_resolved: u64,
+    pub resolution_time: Duration, // Summed over resolved turns, from the start of the turn
+    pub games_finished: u64,
+    pub invalid_moves: BTreeMap<&'static str, u64>, // By reason
+}
+impl Metrics {
+    pub fn record_invalid_move(&mut self, reason: InvalidMove) {
+        *self.invalid_moves.entry(reason_label(reason)).or_default() += 1;
+    }
+
+    pub fn record_turn(&mut self, duration: Duration) {
+        self.turns_resolved += 1;
+        self.resolution_time += duration;
+    }
+
+    pub fn merge(&mut self, other: Metrics) {
+        self.connections += other.connections;
+        self.turns_resolved += other.turns_resolved;
+        self.resolution_time += other.resolution_time;
+        self.games_finished += other.games_finished;
+        for (reason, count) in other.invalid_moves {
+            *self.invalid_moves.entry(reason).or_default() += count;
+        }
+    }
+
+    pub fn render(&self, open_connections: usize, open_rooms: usize, queued: usize) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+        let sample = |value: String| vec![(String::new(), value)];
+        metric(
+            "neurojam_connections_total",
+            "counter",
+            "Websocket connections accepted",
+            &sample(self.connections.to_string()),
+        );
+        metric(
+            "neurojam_connections_open",
+            "gauge",
+            "Websocket connections currently open",
+            &sample(open_connections.to_string()),
+        );
+        metric(
+            "neurojam_rooms_open",
+            "gauge",
+            "Rooms with at least one player",
+            &sample(open_rooms.to_string()),
+        );
+        metric(
+            "neurojam_queue_length",
+            "gauge",
+            "Players waiting for a match",
+            &sample(queued.to_string()),
+        );
+        metric(
+            "neurojam_turns_resolved_total",
+            "counter",
+            "Turns resolved",
+            &sample(self.turns_resolved.to_string()),
+        );
+        let average = match self.turns_resolved {
+            0 => 0.0,
+            turns => self.resolution_time.as_secs_f64() / turns as f64,
+        };
+        metric(
+            "neurojam_turn_resolution_seconds_average",
+            "gauge",
+            "Average time from the start of a turn until it resolved",
+            &sample(average.to_string()),
+        );
+        metric(
+            "neurojam_games_finished_total",
+            "counter",
+            "Games that reached a result",
+            &sample(self.games_finished.to_string()),
+        );
+        let invalid_moves: Vec<_> = self
+            .invalid_moves
+            .iter()
+            .map(|(reason, count)| (format!("{{reason=\"{}\"}}", reason), count.to_string()))
+            .collect();
+        metric(
+            "neurojam_invalid_moves_total",
+            "counter",
+            "Rejected moves by reason",
+            &invalid_moves,
+        );
+        out
+    }
+}
+
+fn reason_label(reason: InvalidMove) -> &'static str {
+    match reason {
+        InvalidMove::Occupied => "occupied",
+        InvalidMove::OccupiedStationary => "occupied_stationary",
+        InvalidMove::OutOfBounds => "out_of_bounds",
+        InvalidMove::Collision(_) => "collision",
+        InvalidMove::Uncommitted => "uncommitted",
+        InvalidMove::CommitmentMismatch => "commitment_mismatch",
+        InvalidMove::Voided(_) => "voided",
+        InvalidMove::NotInProgress => "not_in_progress",
+        InvalidMove::AlreadySubmitted => "already_submitted",
+        InvalidMove::CommitRevealOff => "commit_reveal_off",
+    }
+}
@@ -0,0 +1,94 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use neurojam24_core::{rate, GameResult, Profile};
+use tracing::error;
+
+// Every player's profile by name, kept in a JSON file
+pub struct Profiles {
+    path: PathBuf,
+    profiles: BTreeMap<String, Profile>,
+}
+impl Profiles {
+    // Starts empty if the file doesn't exist yet
+    pub fn load(path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let profiles = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Self { path, profiles })
+    }
+
+    pub fn get(&self, name: &str) -> Profile {
+        self.profiles
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| Profile::new(name.to_string()))
+    }
+
+    // Players with rated games, highest rated first
+    pub fn leaderboard(&self) -> Vec<Profile> {
+        let mut leaderboard: Vec<_> = self
+            .profiles
+            .values()
+            .filter(|profile| profile.rated_games > 0)
+            .cloned()
+            .collect();
+        leaderboard.sort_by(|a, b| b.rating.cmp(&a.rating).then_with(|| a.name.cmp(&b.name)));
+        leaderboard
+    }
+
+    // Records a game between the named players, returning their updated profiles
+    pub fn record(&mut self, names: [&str; 2], result: GameResult, rated: bool) -> [Profile; 2] {
+        let ratings = rate(names.map(|name| self.get(name).rating), result);
+        let profiles = std::array::from_fn(|player_id| {
+            let profile = self
+                .profiles
+                .entry(names[player_id].to_string())
+                .or_insert_with(|| Profile::new(names[player_id].to_string()));
+            profile.record(player_id as u8, result);
+            if rated {
+                profile.rating = ratings[player_id];
+                profile.rated_games += 1;
+            }
+            profile.clone()
+        });
+        self.save();
+        profiles
+    }
+
+    // Written to a temporary file first, so a crash can't leave half a file behind
+    fn save(&self) {
+        let json = serde_json::to_string_pretty(&self.profiles).expect("Profiles serialise");
+        let temporary = self.path.with_extension("json.tmp");
+        let saved =
+            std::fs::write(&temporary, json).and_then(|()| std::fs::rename(&temporary, &self.path));
+        if let Err(error) = saved {
+            error!(%error, "Failed to save profiles");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rated_games_are_saved_and_ranked() {
+        let path = std::env::temp_dir().join(format!("profiles-{}.json", std::process::id()));
+        let mut profiles = Profiles::load(path.clone()).unwrap();
+        profiles.record(["alice", "bob"], GameResult::Win(0), true);
+        profiles.record(["alice", "carol"], GameResult::Draw, false);
+        let profiles = Profiles::load(path.clone()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let alice = profiles.get("alice");
+        assert_eq!((alice.games_played, alice.rated_games), (2, 1));
+        // Unrated players stay off the leaderboard
+        let names: Vec<_> = profiles
+            .leaderboard()
+            .into_iter()
+            .map(|profile| profile.name)
+            .collect();
+        assert_eq!(names, ["alice", "bob"]);
+    }
+}
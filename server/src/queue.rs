@@ -0,0 +1,171 @@
+use std::time::Instant;
+
+use neurojam24_core::{MatchPreferences, RequestId, RoomConfig};
+
+use crate::ConnectionId;
+
+pub struct QueueEntry {
+    pub connection_id: ConnectionId,
+    pub request_id: RequestId,
+    pub preferences: MatchPreferences,
+    pub ruleset: RoomConfig, // The preferred one, or the server's default for players without one
+    pub rating: u32,
+    pub queued_at: Instant,
+    reported_position: Option<usize>, // The last position sent to the player
+}
+impl QueueEntry {
+    pub fn new(
+        connection_id: ConnectionId,
+        request_id: RequestId,
+        preferences: MatchPreferences,
+        ruleset: RoomConfig,
+        rating: u32,
+    ) -> Self {
+        Self {
+            connection_id,
+            request_id,
+            preferences,
+            ruleset,
+            rating,
+            queued_at: Instant::now(),
+            reported_position: None,
+        }
+    }
+
+    // Whether both players accept each other and the same ruleset
+    fn agree(&self, other: &QueueEntry) -> bool {
+        let gap = self.rating.abs_diff(other.rating);
+        let within_band = |entry: &QueueEntry| {
+            entry
+                .preferences
+                .max_rating_gap
+                .is_none_or(|max_gap| gap <= max_gap)
+        };
+        let rated = self.preferences.rated == other.preferences.rated;
+        rated && self.ruleset == other.ruleset && within_band(self) && within_band(other)
+    }
+}
+
+// Players waiting for a match, longest waiting first
+#[derive(Default)]
+pub struct Queue {
+    entries: Vec<QueueEntry>,
+}
+impl Queue {
+    pub fn push(&mut self, entry: QueueEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn remove(&mut self, connection_id: ConnectionId) -> bool {
+        let len = self.entries.len();
+        self.entries
+            .retain(|entry| entry.connection_id != connection_id);
+        self.entries.len() != len
+    }
+
+    pub fn contains(&self, connection_id: ConnectionId) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.connection_id == connection_id)
+    }
+
+    pub fn entries(&self) -> &[QueueEntry] {
+        &self.entries
+    }
+
+    // Takes the pair that has waited the longest among those that accept each other
+    pub fn pop_match(&mut self) -> Option<(QueueEntry, QueueEntry)> {
+        for i in 0..self.entries.len() {
+            for j in i + 1..self.entries.len() {
+                if self.entries[i].agree(&self.entries[j]) {
+                    let second = self.entries.remove(j);
+                    let first = self.entries.remove(i);
+                    return Some((first, second));
+                }
+            }
+        }
+        None
+    }
+
+    // Positions, starting at 1, that changed since they were last reported
+    pub fn position_updates(&mut self) -> Vec<(ConnectionId, usize)> {
+        let mut updates = Vec::new();
+        for (index, entry) in self.entries.iter_mut().enumerate() {
+            let position = index + 1;
+            if entry.reported_position != Some(position) {
+                entry.reported_position = Some(position);
+                updates.push((entry.connection_id, position));
+            }
+        }
+        updates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        connection_id: ConnectionId,
+        rating: u32,
+        preferences: MatchPreferences,
+    ) -> QueueEntry {
+        let ruleset = preferences.ruleset.unwrap_or_default();
+        QueueEntry::new(connection_id, 0, preferences, ruleset, rating)
+    }
+
+    fn pair(queue: &mut Queue) -> Option<(ConnectionId, ConnectionId)> {
+        let (first, second) = queue.pop_match()?;
+        Some((first.connection_id, second.connection_id))
+    }
+
+    #[test]
+    fn pop_match_respects_both_rating_bands() {
+        let narrow = MatchPreferences {
+            max_rating_gap: Some(100),
+            ..Default::default()
+        };
+        let mut queue = Queue::default();
+        queue.push(entry(0, 1500, narrow));
+        queue.push(entry(1, 1700, MatchPreferences::default()));
+        assert_eq!(pair(&mut queue), None);
+        // Within the band of the player waiting longest, who is paired first
+        queue.push(entry(2, 1580, MatchPreferences::default()));
+        assert_eq!(pair(&mut queue), Some((0, 2)));
+        assert_eq!(queue.entries().len(), 1);
+    }
+
+    #[test]
+    fn pop_match_needs_the_same_rated_choice() {
+        let rated = MatchPreferences {
+            rated: true,
+            ..Default::default()
+        };
+        let mut queue = Queue::default();
+        queue.push(entry(0, 1500, rated));
+        queue.push(entry(1, 1500, MatchPreferences::default()));
+        assert_eq!(pair(&mut queue), None);
+        queue.push(entry(2, 1500, rated));
+        assert_eq!(pair(&mut queue), Some((0, 2)));
+    }
+
+    #[test]
+    fn pop_match_agrees_on_a_ruleset() {
+        let ruleset = |commit_reveal| MatchPreferences {
+            ruleset: Some(RoomConfig {
+                commit_reveal,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut queue = Queue::default();
+        queue.push(entry(0, 1500, ruleset(true)));
+        queue.push(entry(1, 1500, MatchPreferences::default()));
+        assert_eq!(pair(&mut queue), None);
+        // Players without a preference play the default, so they can't be talked into another
+        queue.push(entry(2, 1500, ruleset(false)));
+        assert_eq!(pair(&mut queue), Some((1, 2)));
+        queue.push(entry(3, 1500, ruleset(true)));
+        assert_eq!(pair(&mut queue), Some((0, 3)));
+    }
+}
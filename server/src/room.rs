@@ -0,0 +1,1568 @@
+use std::time::{Duration, Instant};
+
+use neurojam24_core::{
+    bot_action, ActionType, ChatMessage, ClientMessage, Commitment, Difficulty, GameResult,
+    GameStatus, InvalidMove, MatchScore, Nonce, PlayerAction, Profile, RejoinToken, Replay,
+    ReplayEvent, ReplayTurn, RequestId, RoomConfig, RoomId, RoomPhase, ServerMessage,
+    TemporalDirection, TimeoutAction,
+};
+use rand::{seq::SliceRandom, Rng};
+use tracing::{debug, error, info, info_span, Span};
+
+use crate::{clock::Clock, metrics::Metrics, save_replay, save_snapshot, ConnectionId};
+
+const START_COUNTDOWN: Duration = Duration::from_secs(3);
+const RECONNECT_GRACE: Duration = Duration::from_secs(30); // Before a dropped player forfeits
+
+// A game between two matched players, and the series of rematches that follows
+pub struct Room {
+    pub id: RoomId,
+    pub rated: bool, // Whether results change ratings
+    config: RoomConfig,
+    pub game_status: GameStatus,
+    inputs: [Option<PlayerAction>; 2],
+    commitments: [Option<Commitment>; 2],
+    nonces: [Option<Nonce>; 2],
+    timed_out: [bool; 2], // Inputs the turn timer chose, marked as such in the replay
+    requests: [Option<RequestId>; 2], // Requests behind the current inputs, to answer late rejections
+    needs_step: bool,
+    pub seats: [Option<ConnectionId>; 2],
+    dropped: [Option<Instant>; 2], // When players whose seats are held for them lost their connection
+    tokens: [RejoinToken; 2],      // Last handed out with each seat, to reclaim it with
+    pub bots: [Option<Difficulty>; 2], // Seats played by the server instead of a connection
+    pub profiles: [Option<Profile>; 2], // Kept after leaving, so results still count
+    pub results: Vec<GameResult>,  // Finished games, for the server to record in profiles
+    pub result: Option<GameResult>,
+    pub outbox: Vec<(u8, ServerMessage)>, // By player, for the server to deliver
+    pub metrics: Metrics,                 // Moved into the server's totals after every update
+    span: Span,
+    replay: Replay,
+    turn_started: Option<Instant>,
+    timer_broadcast: Option<u64>,
+    clocks: Option<[Clock; 2]>,
+    pub phase: RoomPhase,
+    lobby_ready: [bool; 2],
+    countdown_started: Option<Instant>,
+    rematch: [bool; 2],
+    pub score: MatchScore,
+    games_played: u32,
+    draw_offers: [bool; 2],
+    pause_requests: [bool; 2],
+    paused_at: Option<Instant>,
+}
+impl Room {
+    pub fn new(id: RoomId, config: RoomConfig, rated: bool) -> Self {
+        let game_status = GameStatus::initial();
+        let inputs = [None; 2];
+        let commitments = [None; 2];
+        let nonces = [None; 2];
+        let timed_out = [false; 2];
+        let requests = [None; 2];
+        let needs_step = false;
+        let seats = [None; 2];
+        let dropped = [None; 2];
+        let tokens = [0; 2];
+        let bots = [None; 2];
+        let profiles = [None, None];
+        let results = Vec::new();
+        let result = None;
+        let outbox = Vec::new();
+        let metrics = Metrics::default();
+        let span = info_span!(parent: None, "room", id);
+        let replay = Replay::new(config);
+        let turn_started = None;
+        let timer_broadcast = None;
+        let clocks = Self::new_clocks(&config);
+        let phase = RoomPhase::Waiting;
+        let lobby_ready = [false; 2];
+        let countdown_started = None;
+        let rematch = [false; 2];
+        let score = MatchScore::new(config.best_of);
+        let games_played = 0;
+        let draw_offers = [false; 2];
+        let pause_requests = [false; 2];
+        let paused_at = None;
+        Self {
+            id,
+            rated,
+            config,
+            game_status,
+            inputs,
+            commitments,
+            nonces,
+            timed_out,
+            requests,
+            needs_step,
+            seats,
+            dropped,
+            tokens,
+            bots,
+            profiles,
+            results,
+            result,
+            outbox,
+            metrics,
+            span,
+            replay,
+            turn_started,
+            timer_broadcast,
+            clocks,
+            phase,
+            lobby_ready,
+            countdown_started,
+            rematch,
+            score,
+            games_played,
+            draw_offers,
+            pause_requests,
+            paused_at,
+        }
+    }
+
+    fn new_clocks(config: &RoomConfig) -> Option<[Clock; 2]> {
+        config
+            .time_bank
+            .map(|secs| std::array::from_fn(|_| Clock::new(Duration::from_secs(secs as u64))))
+    }
+
+    // Which seats are taken, by a player or a bot
+    pub fn connected(&self) -> [bool; 2] {
+        std::array::from_fn(|i| self.seats[i].is_some() || self.bots[i].is_some())
+    }
+
+    // Whether nobody is left and no seat is held for anyone, so the room can close
+    pub fn is_abandoned(&self) -> bool {
+        self.seats.iter().all(Option::is_none) && self.dropped.iter().all(Option::is_none)
+    }
+
+    pub fn join(
+        &mut self,
+        connection_id: ConnectionId,
+        request_id: RequestId,
+        profile: Profile,
+    ) -> Option<u8> {
+        let span = self.span.clone();
+        let _entered = span.enter();
+        let player_id = (0..2).find(|&i| !self.connected()[i] && self.dropped[i].is_none())?;
+        info!(player_id, name = profile.name, "Player joined");
+        self.profiles[player_id] = Some(profile);
+        self.seat(player_id as u8, connection_id, request_id);
+        Some(player_id as u8)
+    }
+
+    // Gives a player who lost their connection their seat back, if it's still held for them or
+    // taken by the old connection the server hasn't noticed is gone. Returns the seat, and the
+    // connection it was taken from
+    pub fn rejoin(
+        &mut self,
+        connection_id: ConnectionId,
+        request_id: RequestId,
+        token: RejoinToken,
+    ) -> Option<(u8, Option<ConnectionId>)> {
+        let span = self.span.clone();
+        let _entered = span.enter();
+        let player_id = (0..2).find(|&i| {
+            let held = self.dropped[i].is_some() || self.seats[i].is_some();
+            held && self.tokens[i] == token
+        })?;
+        info!(player_id, "Player came back");
+        let replaced = self.seats[player_id];
+        self.dropped[player_id] = None;
+        self.seat(player_id as u8, connection_id, request_id);
+        // What was submitted before dropping still stands
+        for other in 0..2 {
+            if self.inputs[other].is_some() || self.commitments[other].is_some() {
+                self.send(player_id as u8, ServerMessage::PlayerReady(other as u8));
+            }
+        }
+        Some((player_id as u8, replaced))
+    }
+
+    fn seat(&mut self, player_id: u8, connection_id: ConnectionId, request_id: RequestId) {
+        self.seats[player_id as usize] = Some(connection_id);
+        // A new token every time, so an old connection can't take the seat back
+        let token = rand::random();
+        self.tokens[player_id as usize] = token;
+        self.send(
+            player_id,
+            ServerMessage::Assign(request_id, self.id, player_id, token),
+        );
+        self.send(player_id, ServerMessage::Config(self.config));
+        self.send_state(player_id);
+        if self.phase == RoomPhase::Waiting && self.connected().iter().all(|&c| c) {
+            self.set_phase(RoomPhase::ReadyCheck);
+        } else {
+            self.send(player_id, ServerMessage::Phase(self.phase));
+        }
+        self.send(player_id, ServerMessage::LobbyReady(self.lobby_ready));
+        self.send(player_id, ServerMessage::RematchOffers(self.rematch));
+        self.send(player_id, ServerMessage::Score(self.score));
+        self.send(player_id, ServerMessage::DrawOffers(self.draw_offers));
+        self.send(player_id, ServerMessage::PauseRequests(self.pause_requests));
+        self.broadcast(ServerMessage::Connected(self.connected()));
+        self.broadcast(ServerMessage::Profiles(self.profiles.clone()));
+    }
+
+    // Seats a bot of the given difficulty, which plays without a connection
+    pub fn add_bot(&mut self, difficulty: Difficulty) -> Option<u8> {
+        let span = self.span.clone();
+        let _entered = span.enter();
+        let player_id = (0..2).find(|&i| !self.connected()[i])?;
+        self.bots[player_id] = Some(difficulty);
+        info!(player_id, %difficulty, "Bot joined");
+        self.profiles[player_id] = Some(Profile::new(format!("{} bot", difficulty)));
+        if self.phase == RoomPhase::Waiting && self.connected().iter().all(|&c| c) {
+            self.set_phase(RoomPhase::ReadyCheck);
+        }
+        self.broadcast(ServerMessage::Connected(self.connected()));
+        self.broadcast(ServerMessage::Profiles(self.profiles.clone()));
+        Some(player_id as u8)
+    }
+
+    pub fn set_profiles(&mut self, profiles: [Profile; 2]) {
+        self.profiles = profiles.map(Some);
+        self.broadcast(ServerMessage::Profiles(self.profiles.clone()));
+    }
+
+    // With `hold_seat`, the player lost their connection mid-game, so the seat is kept for them
+    // to come back to until RECONNECT_GRACE runs out. Leaving a game on purpose forfeits it
+    pub fn leave(&mut self, player_id: u8, hold_seat: bool) {
+        let span = self.span.clone();
+        let _entered = span.enter();
+        let in_game = matches!(self.phase, RoomPhase::InProgress | RoomPhase::Paused);
+        match hold_seat && in_game {
+            true => {
+                info!(player_id, "Player dropped, holding their seat");
+                self.dropped[player_id as usize] = Some(Instant::now());
+            }
+            false => info!(player_id, "Player left"),
+        }
+        if in_game && !hold_seat {
+            self.finish(GameResult::Resignation(player_id));
+        }
+        self.seats[player_id as usize] = None;
+        self.broadcast(ServerMessage::Connected(self.connected()));
+        if matches!(self.phase, RoomPhase::ReadyCheck | RoomPhase::Countdown) {
+            self.lobby_ready = [false; 2];
+            self.broadcast(ServerMessage::LobbyReady(self.lobby_ready));
+            self.set_phase(RoomPhase::Waiting);
+        }
+        if self.rematch.iter().any(|&r| r) {
+            self.rematch = [false; 2];
+            self.broadcast(ServerMessage::RematchOffers(self.rematch));
+        }
+    }
+
+    // Passes on an already validated chat message to everyone in the room
+    pub fn chat(&mut self, player_id: u8, chat: ChatMessage) {
+        let span = self.span.clone();
+        let _entered = span.enter();
+        info!(player_id, %chat, "Chat");
+        self.broadcast(ServerMessage::Chat(player_id, chat));
+    }
+
+    pub fn receive(&mut self, player_id: u8, message: ClientMessage) {
+        let span = self.span.clone();
+        let _entered = span.enter();
+        match message {
+            // Handled by the server, since they aren't about this room
+            ClientMessage::FindMatch(..)
+            | ClientMessage::Rejoin(..)
+            | ClientMessage::CancelMatch
+            | ClientMessage::Leaderboard(_)
+            | ClientMessage::Leave
+            | ClientMessage::Chat(_) => {}
+            ClientMessage::Action(request_id, action) => {
+                debug!(player_id, "Received move");
+                self.submit(player_id, request_id, action);
+            }
+            ClientMessage::Commit(request_id, commitment) => {
+                debug!(player_id, "Received commitment");
+                self.commit(player_id, request_id, commitment);
+            }
+            ClientMessage::Reveal(request_id, action, nonce) => {
+                debug!(player_id, "Received reveal");
+                self.reveal(player_id, request_id, action, nonce);
+            }
+            ClientMessage::Ready => self.set_ready(player_id, true),
+            ClientMessage::Unready => self.set_ready(player_id, false),
+            ClientMessage::OfferRematch => self.offer_rematch(player_id, false),
+            ClientMessage::AcceptRematch => self.offer_rematch(player_id, true),
+            ClientMessage::Resign => self.resign(player_id),
+            ClientMessage::OfferDraw => self.offer_draw(player_id, false),
+            ClientMessage::AcceptDraw => self.offer_draw(player_id, true),
+            ClientMessage::Pause => self.request_pause(player_id, false),
+            ClientMessage::Resume => self.request_pause(player_id, true),
+        }
+    }
+
+    fn offer_rematch(&mut self, player_id: u8, accept: bool) {
+        if self.phase != RoomPhase::Finished {
+            return;
+        }
+        if accept && !self.rematch[1 - player_id as usize] {
+            // There's no offer to accept
+            return;
+        }
+        self.rematch[player_id as usize] = true;
+        self.broadcast(ServerMessage::RematchOffers(self.rematch));
+        if self.rematch.iter().all(|&r| r) {
+            self.reset();
+        }
+    }
+
+    fn resign(&mut self, player_id: u8) {
+        if matches!(self.phase, RoomPhase::InProgress | RoomPhase::Paused) {
+            self.finish(GameResult::Resignation(player_id));
+        }
+    }
+
+    fn offer_draw(&mut self, player_id: u8, accept: bool) {
+        if !matches!(self.phase, RoomPhase::InProgress | RoomPhase::Paused) {
+            return;
+        }
+        if accept && !self.draw_offers[1 - player_id as usize] {
+            return;
+        }
+        if !accept {
+            self.replay.push_event(ReplayEvent::DrawOffered(player_id));
+        }
+        self.draw_offers[player_id as usize] = true;
+        self.broadcast(ServerMessage::DrawOffers(self.draw_offers));
+        if self.draw_offers.iter().all(|&o| o) {
+            self.finish(GameResult::AgreedDraw);
+        }
+    }
+
+    fn request_pause(&mut self, player_id: u8, resume: bool) {
+        let (from, to, event) = match resume {
+            true => (
+                RoomPhase::Paused,
+                RoomPhase::InProgress,
+                ReplayEvent::Resumed,
+            ),
+            false => (
+                RoomPhase::InProgress,
+                RoomPhase::Paused,
+                ReplayEvent::Paused,
+            ),
+        };
+        if self.phase != from {
+            return;
+        }
+        self.pause_requests[player_id as usize] = true;
+        if self.pause_requests.iter().all(|&r| r) {
+            self.pause_requests = [false; 2];
+            self.replay.push_event(event);
+            self.set_phase(to);
+        }
+        self.broadcast(ServerMessage::PauseRequests(self.pause_requests));
+    }
+
+    fn reset(&mut self) {
+        if let Some(winner) = self.score.winner() {
+            info!(winner, "Series won, starting a new one");
+            self.score = MatchScore::new(self.config.best_of);
+        }
+        self.games_played += 1;
+        let swapped = self.config.swap_spawns && self.games_played % 2 == 1;
+        self.new_game(swapped);
+        // Agreeing to a rematch counts as readying up
+        self.lobby_ready = [true; 2];
+        self.broadcast(ServerMessage::LobbyReady(self.lobby_ready));
+        self.set_phase(RoomPhase::Countdown);
+    }
+
+    // Discards the current game without a result, going back to the ready check
+    pub fn force_reset(&mut self) {
+        self.new_game(self.replay.spawns_swapped);
+        // Seats held for dropped players go with the game they were held for
+        self.dropped = [None; 2];
+        self.countdown_started = None;
+        self.lobby_ready = [false; 2];
+        self.broadcast(ServerMessage::LobbyReady(self.lobby_ready));
+        match self.connected().iter().all(|&c| c) {
+            true => self.set_phase(RoomPhase::ReadyCheck),
+            false => self.set_phase(RoomPhase::Waiting),
+        }
+    }
+
+    fn new_game(&mut self, swapped: bool) {
+        self.game_status = match swapped {
+            true => GameStatus::swapped(),
+            false => GameStatus::initial(),
+        };
+        self.inputs = [None; 2];
+        self.commitments = [None; 2];
+        self.nonces = [None; 2];
+        self.timed_out = [false; 2];
+        self.requests = [None; 2];
+        self.result = None;
+        self.replay = Replay::new(self.config);
+        self.replay.spawns_swapped = swapped;
+        self.clocks = Self::new_clocks(&self.config);
+        self.draw_offers = [false; 2];
+        self.pause_requests = [false; 2];
+        self.broadcast(ServerMessage::DrawOffers(self.draw_offers));
+        self.broadcast(ServerMessage::PauseRequests(self.pause_requests));
+        self.rematch = [false; 2];
+        self.broadcast(ServerMessage::RematchOffers(self.rematch));
+        self.broadcast(ServerMessage::Score(self.score));
+        self.turn_started = None;
+        self.paused_at = None;
+        self.needs_step = false;
+        self.broadcast_state();
+    }
+
+    fn set_ready(&mut self, player_id: u8, ready: bool) {
+        if !matches!(self.phase, RoomPhase::ReadyCheck | RoomPhase::Countdown) {
+            return;
+        }
+        self.lobby_ready[player_id as usize] = ready;
+        self.broadcast(ServerMessage::LobbyReady(self.lobby_ready));
+        if self.lobby_ready.iter().all(|&r| r) {
+            self.set_phase(RoomPhase::Countdown);
+        } else {
+            self.set_phase(RoomPhase::ReadyCheck);
+        }
+    }
+
+    fn set_phase(&mut self, phase: RoomPhase) {
+        if self.phase == phase {
+            return;
+        }
+        info!(?phase, "Room phase changed");
+        let previous = std::mem::replace(&mut self.phase, phase);
+        self.broadcast(ServerMessage::Phase(phase));
+        match phase {
+            RoomPhase::Countdown => {
+                self.countdown_started = Some(Instant::now());
+                self.broadcast(ServerMessage::Countdown(START_COUNTDOWN.as_millis() as u64));
+            }
+            RoomPhase::InProgress if previous == RoomPhase::Paused => {
+                // Time spent paused doesn't count towards the turn timer
+                if let Some(paused_at) = self.paused_at.take() {
+                    self.turn_started = self.turn_started.map(|t| t + paused_at.elapsed());
+                    self.timer_broadcast = None;
+                }
+                self.start_clocks();
+            }
+            RoomPhase::Paused => {
+                self.paused_at = Some(Instant::now());
+                if let Some(clocks) = &mut self.clocks {
+                    clocks.iter_mut().for_each(Clock::stop);
+                }
+                self.broadcast_clocks();
+            }
+            RoomPhase::InProgress => {
+                self.broadcast(ServerMessage::Start(self.replay.spawns_swapped));
+                self.start_turn();
+            }
+            _ => self.countdown_started = None,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        let span = self.span.clone();
+        let _entered = span.enter();
+        self.tick_lobby();
+        self.tick_dropped();
+        self.tick_bots();
+        self.tick_timer();
+        self.tick_clocks();
+        if self.needs_step {
+            self.step();
+            self.needs_step = false;
+        }
+    }
+
+    fn tick_lobby(&mut self) {
+        if let Some(countdown_started) = self.countdown_started {
+            if countdown_started.elapsed() >= START_COUNTDOWN {
+                self.countdown_started = None;
+                self.set_phase(RoomPhase::InProgress);
+            }
+        }
+    }
+
+    fn tick_dropped(&mut self) {
+        for player_id in 0..2 {
+            let Some(dropped) = self.dropped[player_id] else {
+                continue;
+            };
+            if dropped.elapsed() >= RECONNECT_GRACE {
+                info!(player_id, "Player didn't come back in time");
+                self.dropped[player_id] = None;
+                self.finish(GameResult::Resignation(player_id as u8));
+            }
+        }
+    }
+
+    // Bots ready up, agree to whatever their opponent asks for besides a draw, and move as soon
+    // as a turn starts
+    fn tick_bots(&mut self) {
+        for player_id in 0..2 {
+            let Some(difficulty) = self.bots[player_id] else {
+                continue;
+            };
+            let other = 1 - player_id;
+            match self.phase {
+                RoomPhase::ReadyCheck if !self.lobby_ready[player_id] => {
+                    self.set_ready(player_id as u8, true);
+                }
+                RoomPhase::Finished if self.rematch[other] && !self.rematch[player_id] => {
+                    self.offer_rematch(player_id as u8, true);
+                }
+                RoomPhase::InProgress if self.pause_requests[other] => {
+                    self.request_pause(player_id as u8, false);
+                }
+                RoomPhase::Paused if self.pause_requests[other] => {
+                    self.request_pause(player_id as u8, true);
+                }
+                RoomPhase::InProgress if self.inputs[player_id].is_none() => {
+                    self.bot_move(player_id, difficulty);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn bot_move(&mut self, player_id: usize, difficulty: Difficulty) {
+        let mut rng = rand::thread_rng();
+        let Some(action) = bot_action(&self.game_status, player_id, difficulty, &mut rng) else {
+            return; // Only before the first turn, as the game ends once a player is stuck
+        };
+        debug!(player_id, %action, "Bot moved");
+        if self.config.commit_reveal {
+            let nonce: Nonce = rng.gen();
+            let commitment = Commitment::new(player_id as u8, action, nonce);
+            self.commitments[player_id] = Some(commitment);
+            self.nonces[player_id] = Some(nonce);
+        }
+        self.set_input(player_id as u8, action)
+            .expect("Bots only choose legal actions");
+        if self.config.commit_reveal && self.all_submitted() {
+            self.request_reveals();
+        }
+        self.needs_step = true;
+    }
+
+    pub fn set_input(&mut self, player_id: u8, action: PlayerAction) -> Result<(), InvalidMove> {
+        // Collisions depend on both actions, so those are only caught when stepping
+        let source = self.game_status.player_locations[player_id as usize];
+        self.game_status
+            .board
+            .calculate_action(player_id as usize, action, source)?;
+        self.inputs[player_id as usize] = Some(action);
+        self.broadcast(ServerMessage::PlayerReady(player_id));
+        self.update_clock(player_id as usize, Clock::stop);
+        Ok(())
+    }
+
+    pub fn send(&mut self, player_id: u8, message: ServerMessage) {
+        self.outbox.push((player_id, message));
+    }
+
+    pub fn broadcast(&mut self, message: ServerMessage) {
+        for player_id in 0..2 {
+            if self.seats[player_id as usize].is_some() {
+                self.send(player_id, message.clone());
+            }
+        }
+    }
+
+    // Keeps a game that won't be finished, e.g. because the server is shutting down
+    pub fn save_unfinished(&self) {
+        let _entered = self.span.enter();
+        if matches!(self.phase, RoomPhase::InProgress | RoomPhase::Paused) {
+            info!(turn = self.game_status.turn(), "Saving the unfinished game");
+            if let Err(error) = save_snapshot(self.id, &self.replay, &self.game_status) {
+                error!(%error, "Failed to save the unfinished game");
+            }
+        }
+    }
+
+    fn send_state(&mut self, player_id: u8) {
+        self.send(
+            player_id,
+            ServerMessage::Display(Box::new(self.game_status.display())),
+        );
+        self.send(
+            player_id,
+            ServerMessage::Stati(self.game_status.player_stati),
+        );
+        if let Some(clocks) = &self.clocks {
+            let clocks = clocks.each_ref().map(Clock::status);
+            self.send(player_id, ServerMessage::Clocks(clocks));
+        }
+        if let Some(result) = self.result {
+            self.send(player_id, ServerMessage::Result(result));
+        }
+    }
+
+    fn broadcast_state(&mut self) {
+        for player_id in 0..2 {
+            self.send_state(player_id);
+        }
+    }
+
+    fn submit(&mut self, player_id: u8, request_id: RequestId, action: PlayerAction) {
+        if self.phase != RoomPhase::InProgress {
+            self.refuse(player_id, request_id, InvalidMove::NotInProgress);
+            return;
+        }
+        if self.config.commit_reveal {
+            // Nothing was submitted, so the opponent's commitment stands
+            self.refuse(player_id, request_id, InvalidMove::Uncommitted);
+            return;
+        }
+        self.accept_input(player_id, request_id, action);
+    }
+
+    fn commit(&mut self, player_id: u8, request_id: RequestId, commitment: Commitment) {
+        if self.phase != RoomPhase::InProgress {
+            self.refuse(player_id, request_id, InvalidMove::NotInProgress);
+            return;
+        }
+        if !self.config.commit_reveal {
+            self.refuse(player_id, request_id, InvalidMove::CommitRevealOff);
+            return;
+        }
+        // Also covers an action the turn timer already chose for the player
+        let submitted = self.inputs[player_id as usize].is_some();
+        let slot = &mut self.commitments[player_id as usize];
+        if slot.is_some() || submitted {
+            self.refuse(player_id, request_id, InvalidMove::AlreadySubmitted);
+            return;
+        }
+        *slot = Some(commitment);
+        self.requests[player_id as usize] = Some(request_id);
+        self.send(player_id, ServerMessage::ActionAccepted(request_id));
+        self.broadcast(ServerMessage::PlayerReady(player_id));
+        self.update_clock(player_id as usize, Clock::stop);
+        if self.all_submitted() {
+            self.request_reveals();
+        }
+    }
+
+    // Committing only pauses a clock until everyone has committed, so players who don't reveal
+    // still run out of time
+    fn request_reveals(&mut self) {
+        self.broadcast(ServerMessage::RevealRequested);
+        self.start_clocks();
+    }
+
+    fn reveal(&mut self, player_id: u8, request_id: RequestId, action: PlayerAction, nonce: Nonce) {
+        if self.phase != RoomPhase::InProgress {
+            self.refuse(player_id, request_id, InvalidMove::NotInProgress);
+            return;
+        }
+        if !self.all_submitted() {
+            self.refuse(player_id, request_id, InvalidMove::Uncommitted);
+            return;
+        }
+        let Some(commitment) = self.commitments[player_id as usize] else {
+            // The timer ran out before this player committed, so their action was chosen for them
+            self.refuse(player_id, request_id, InvalidMove::Uncommitted);
+            return;
+        };
+        if !commitment.verify(player_id, action, nonce) {
+            self.reject_input(player_id, request_id, InvalidMove::CommitmentMismatch);
+            return;
+        }
+        self.nonces[player_id as usize] = Some(nonce);
+        self.accept_input(player_id, request_id, action);
+    }
+
+    // Whether every player has committed to an action, or had one submitted for them
+    fn all_submitted(&self) -> bool {
+        (0..2).all(|player_id| {
+            self.commitments[player_id].is_some() || self.inputs[player_id].is_some()
+        })
+    }
+
+    fn accept_input(&mut self, player_id: u8, request_id: RequestId, action: PlayerAction) {
+        match self.set_input(player_id, action) {
+            Ok(()) => {
+                self.requests[player_id as usize] = Some(request_id);
+                self.send(player_id, ServerMessage::ActionAccepted(request_id));
+                self.needs_step = true;
+            }
+            Err(reason) => self.reject_input(player_id, request_id, reason),
+        }
+    }
+
+    fn reject_input(&mut self, player_id: u8, request_id: RequestId, reason: InvalidMove) {
+        let mut reasons = [None; 2];
+        reasons[player_id as usize] = Some(reason);
+        self.requests[player_id as usize] = Some(request_id);
+        self.reject_inputs(reasons);
+    }
+
+    // Answers a request without touching inputs, commitments or clocks, e.g. one sent while
+    // paused or before reveals were asked for, which mustn't undo what either player submitted
+    fn refuse(&mut self, player_id: u8, request_id: RequestId, reason: InvalidMove) {
+        info!(player_id, %reason, "Invalid move");
+        self.metrics.record_invalid_move(reason);
+        self.send(
+            player_id,
+            ServerMessage::InvalidMove(Some(request_id), reason),
+        );
+    }
+
+    fn reject_inputs(&mut self, mut reasons: [Option<InvalidMove>; 2]) {
+        if self.config.commit_reveal {
+            // Reveals can't be taken back, so any rejection restarts the turn for both players
+            for player_id in 0..2 {
+                let other = 1 - player_id;
+                let submitted =
+                    self.commitments[player_id].is_some() || self.inputs[player_id].is_some();
+                if submitted && reasons[player_id].is_none() && reasons[other].is_some() {
+                    reasons[player_id] = Some(InvalidMove::Voided(other as u8));
+                }
+            }
+        }
+        for (player_id, reason) in reasons.into_iter().enumerate() {
+            if let Some(reason) = reason {
+                info!(player_id, %reason, "Invalid move");
+                self.metrics.record_invalid_move(reason);
+                self.inputs[player_id] = None;
+                self.commitments[player_id] = None;
+                self.nonces[player_id] = None;
+                self.timed_out[player_id] = false;
+                let request_id = self.requests[player_id].take();
+                self.send(
+                    player_id as u8,
+                    ServerMessage::InvalidMove(request_id, reason),
+                );
+                if self.turn_started.is_some() {
+                    self.update_clock(player_id, Clock::start);
+                }
+            }
+        }
+    }
+
+    fn start_turn(&mut self) {
+        if self.phase == RoomPhase::InProgress {
+            self.turn_started = Some(Instant::now());
+            self.timer_broadcast = None;
+            self.start_clocks();
+        }
+    }
+
+    // Starts the clocks of players who haven't chosen an action yet, or have yet to reveal it
+    fn start_clocks(&mut self) {
+        let revealing = self.all_submitted();
+        if let Some(clocks) = &mut self.clocks {
+            for (player_id, clock) in clocks.iter_mut().enumerate() {
+                let committed = self.commitments[player_id].is_some() && !revealing;
+                if self.inputs[player_id].is_none() && !committed {
+                    clock.start();
+                }
+            }
+            self.broadcast_clocks();
+        }
+    }
+
+    fn update_clock(&mut self, player_id: usize, update: impl FnOnce(&mut Clock)) {
+        if let Some(clocks) = &mut self.clocks {
+            update(&mut clocks[player_id]);
+            self.broadcast_clocks();
+        }
+    }
+
+    fn broadcast_clocks(&mut self) {
+        if let Some(clocks) = &self.clocks {
+            let clocks = clocks.each_ref().map(Clock::status);
+            self.broadcast(ServerMessage::Clocks(clocks));
+        }
+    }
+
+    fn tick_clocks(&mut self) {
+        let Some(clocks) = &self.clocks else {
+            return;
+        };
+        if self.result.is_some() || self.phase != RoomPhase::InProgress {
+            return;
+        }
+        match clocks.each_ref().map(Clock::is_flagged) {
+            [true, true] => self.finish(GameResult::Draw),
+            [true, false] => self.finish(GameResult::Timeout(0)),
+            [false, true] => self.finish(GameResult::Timeout(1)),
+            [false, false] => {}
+        }
+    }
+
+    fn tick_timer(&mut self) {
+        let (Some(turn_started), Some(turn_time)) = (self.turn_started, self.config.turn_time)
+        else {
+            return;
+        };
+        if self.phase != RoomPhase::InProgress {
+            return;
+        }
+        let turn_time = Duration::from_secs(turn_time as u64);
+        let elapsed = turn_started.elapsed();
+        if elapsed >= turn_time {
+            self.timeout();
+            return;
+        }
+        let remaining = turn_time - elapsed;
+        if self.timer_broadcast != Some(remaining.as_secs()) {
+            self.timer_broadcast = Some(remaining.as_secs());
+            self.broadcast(ServerMessage::TurnTimer(remaining.as_millis() as u64));
+        }
+    }
+
+    fn timeout(&mut self) {
+        // Reveals only start once everyone has committed, so players who committed in time are
+        // asked to reveal instead of being overruled, unless they were asked already and stalled
+        let revealing = self.all_submitted();
+        let mut forfeits = [false; 2];
+        for (player_id, forfeit) in forfeits.iter_mut().enumerate() {
+            let committed = self.commitments[player_id].is_some();
+            if self.inputs[player_id].is_some() || (committed && !revealing) {
+                continue;
+            }
+            info!(player_id, "Turn timer ran out");
+            self.commitments[player_id] = None;
+            self.nonces[player_id] = None;
+            self.requests[player_id] = None;
+            match self.fallback_action(player_id) {
+                Some(action) => {
+                    self.set_input(player_id as u8, action)
+                        .expect("Fallback actions are legal");
+                    self.timed_out[player_id] = true;
+                }
+                None => *forfeit = true,
+            }
+        }
+        match forfeits {
+            [true, true] => self.finish(GameResult::Draw),
+            [true, false] => self.finish(GameResult::Win(1)),
+            [false, true] => self.finish(GameResult::Win(0)),
+            [false, false] => {
+                if self.config.commit_reveal
+                    && !revealing
+                    && !self.inputs.iter().all(Option::is_some)
+                {
+                    self.broadcast(ServerMessage::RevealRequested);
+                }
+                // A fresh deadline for reveals, or in case the fallback collides and the turn is
+                // replayed
+                self.start_turn();
+                self.needs_step = true;
+            }
+        }
+    }
+
+    fn fallback_action(&self, player_id: usize) -> Option<PlayerAction> {
+        let legal = self.game_status.legal_actions(player_id);
+        match self.config.timeout_action {
+            TimeoutAction::Wait => legal
+                .iter()
+                .find(|action| {
+                    action.action_type == ActionType::Attack
+                        && action.direction.temporal == TemporalDirection::Forward
+                })
+                .or(legal.first())
+                .copied(),
+            TimeoutAction::Random => legal.choose(&mut rand::thread_rng()).copied(),
+            TimeoutAction::Forfeit => None,
+        }
+    }
+
+    fn step(&mut self) {
+        if !self.inputs.iter().all(|i| i.is_some()) {
+            return;
+        }
+        let inputs = self.inputs.map(|i| i.expect("verified"));
+        match self.game_status.push_actions(inputs) {
+            Ok(game_result) => {
+                if let Some(turn_started) = self.turn_started {
+                    self.metrics.record_turn(turn_started.elapsed());
+                }
+                let commitments = std::array::from_fn(|i| self.commitments[i].zip(self.nonces[i]));
+                self.replay.push(ReplayTurn {
+                    actions: inputs,
+                    commitments,
+                    timed_out: self.timed_out,
+                });
+                self.broadcast(ServerMessage::Turn(inputs));
+                self.inputs = [None; 2];
+                self.commitments = [None; 2];
+                self.nonces = [None; 2];
+                self.timed_out = [false; 2];
+                self.requests = [None; 2];
+                if self.draw_offers.iter().any(|&o| o) {
+                    // Draw offers only stand until the next turn resolves
+                    self.draw_offers = [false; 2];
+                    self.broadcast(ServerMessage::DrawOffers(self.draw_offers));
+                }
+                let game_result = game_result.or_else(|| self.game_status.stuck_result());
+                match game_result {
+                    Some(result) if self.result.is_none() => self.finish(result),
+                    _ => {
+                        self.result = game_result;
+                        self.broadcast_state();
+                        let increment = Duration::from_secs(self.config.increment as u64);
+                        if let Some(clocks) = &mut self.clocks {
+                            clocks.iter_mut().for_each(|clock| clock.add(increment));
+                        }
+                        self.start_turn();
+                    }
+                }
+            }
+            Err(error) => match error {
+                neurojam24_core::Error::InvalidMove(reasons) => {
+                    self.reject_inputs(reasons);
+                }
+            },
+        }
+    }
+
+    fn finish(&mut self, result: GameResult) {
+        self.result = Some(result);
+        self.dropped = [None; 2]; // Nothing is left to come back to
+        self.set_phase(RoomPhase::Finished);
+        self.turn_started = None;
+        self.paused_at = None;
+        if let Some(clocks) = &mut self.clocks {
+            clocks.iter_mut().for_each(Clock::stop);
+        }
+        info!(%result, "Game finished");
+        self.score.record(result);
+        self.results.push(result);
+        self.metrics.games_finished += 1;
+        self.broadcast(ServerMessage::Score(self.score));
+        self.broadcast_state();
+        self.replay.result = Some(result);
+        if let Err(error) = save_replay(self.id, &self.replay) {
+            error!(%error, "Failed to save replay");
+        }
+        self.broadcast(ServerMessage::Replay(self.replay.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use neurojam24_core::{Direction, SpatialDirection};
+
+    use super::*;
+
+    fn action(spatial: SpatialDirection, temporal: TemporalDirection) -> PlayerAction {
+        PlayerAction {
+            direction: Direction { spatial, temporal },
+            action_type: ActionType::Move,
+        }
+    }
+
+    fn left() -> PlayerAction {
+        action(SpatialDirection::Left, TemporalDirection::Forward)
+    }
+
+    fn profile(connection_id: ConnectionId) -> Profile {
+        Profile::new(format!("player{}", connection_id))
+    }
+
+    // A room with both players seated, still in the ready check
+    fn seated(config: RoomConfig) -> Room {
+        let mut room = Room::new(0, config, false);
+        for connection_id in 0..2 {
+            room.join(connection_id, 0, profile(connection_id));
+        }
+        room
+    }
+
+    // A room with both players seated and the game underway
+    fn playing(config: RoomConfig) -> Room {
+        let mut room = seated(config);
+        room.outbox.clear();
+        room.phase = RoomPhase::InProgress;
+        room
+    }
+
+    fn room(commit_reveal: bool) -> Room {
+        playing(RoomConfig {
+            commit_reveal,
+            ..Default::default()
+        })
+    }
+
+    fn timed(timeout_action: TimeoutAction) -> Room {
+        playing(RoomConfig {
+            turn_time: Some(10),
+            timeout_action,
+            ..Default::default()
+        })
+    }
+
+    fn drain(room: &mut Room) -> [Vec<ServerMessage>; 2] {
+        let mut outboxes = [Vec::new(), Vec::new()];
+        for (player_id, message) in room.outbox.drain(..) {
+            outboxes[player_id as usize].push(message);
+        }
+        outboxes
+    }
+
+    #[test]
+    fn set_input_keeps_valid_moves() {
+        let mut room = room(false);
+        assert_eq!(room.set_input(0, left()), Ok(()));
+        assert_eq!(room.inputs, [Some(left()), None]);
+        // Both players see who is ready, but not the move itself
+        for outbox in drain(&mut room) {
+            assert!(matches!(outbox[..], [ServerMessage::PlayerReady(0)]));
+        }
+    }
+
+    #[test]
+    fn set_input_rejects_moves_off_the_board() {
+        let mut room = room(false);
+        // Players start at the beginning of time
+        let back = action(SpatialDirection::Left, TemporalDirection::Backward);
+        assert_eq!(room.set_input(1, back), Err(InvalidMove::OutOfBounds));
+        assert_eq!(room.inputs, [None; 2]);
+        assert!(drain(&mut room).iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn reject_inputs_only_tells_the_offending_player() {
+        let mut room = room(false);
+        room.inputs = [Some(left()); 2];
+        room.reject_inputs([None, Some(InvalidMove::OutOfBounds)]);
+        assert_eq!(room.inputs, [Some(left()), None]);
+        let [first, second] = drain(&mut room);
+        assert!(first.is_empty());
+        assert!(matches!(
+            second[..],
+            [ServerMessage::InvalidMove(None, InvalidMove::OutOfBounds)]
+        ));
+    }
+
+    #[test]
+    fn submit_needs_a_commitment_in_commit_reveal() {
+        let mut room = room(true);
+        room.submit(0, 1, left());
+        assert_eq!(room.inputs, [None; 2]);
+        let [first, _] = drain(&mut room);
+        assert!(matches!(
+            first[..],
+            [ServerMessage::InvalidMove(
+                Some(1),
+                InvalidMove::Uncommitted
+            )]
+        ));
+    }
+
+    #[test]
+    fn commit_reveal_plays_verified_turns() {
+        let mut room = room(true);
+        let nonces = [[1; 16], [2; 16]];
+        for player_id in 0..2 {
+            room.commit(
+                player_id,
+                1,
+                Commitment::new(player_id, left(), nonces[player_id as usize]),
+            );
+        }
+        let [first, _] = drain(&mut room);
+        assert!(matches!(first.last(), Some(ServerMessage::RevealRequested)));
+        for player_id in 0..2 {
+            room.reveal(player_id, 2, left(), nonces[player_id as usize]);
+        }
+        room.step();
+        assert_eq!(room.replay.turns.len(), 1);
+        assert!(room.replay.verify());
+        assert_eq!(room.commitments, [None; 2]);
+    }
+
+    #[test]
+    fn mismatched_reveals_void_the_turn() {
+        let mut room = room(true);
+        let nonce = [1; 16];
+        for player_id in 0..2 {
+            room.commit(player_id, 1, Commitment::new(player_id, left(), nonce));
+        }
+        room.reveal(0, 2, left(), nonce);
+        drain(&mut room);
+        room.reveal(1, 2, left(), [9; 16]);
+        assert_eq!(room.commitments, [None; 2]);
+        assert_eq!(room.inputs, [None; 2]);
+        let [first, second] = drain(&mut room);
+        assert!(matches!(
+            first[..],
+            [ServerMessage::InvalidMove(Some(2), InvalidMove::Voided(1))]
+        ));
+        assert!(matches!(
+            second[..],
+            [ServerMessage::InvalidMove(
+                Some(2),
+                InvalidMove::CommitmentMismatch
+            )]
+        ));
+    }
+
+    #[test]
+    fn stray_moves_leave_the_opponents_commitment_alone() {
+        let mut room = room(true);
+        let commitment = Commitment::new(1, left(), [1; 16]);
+        room.commit(1, 1, commitment);
+        drain(&mut room);
+        room.submit(0, 2, left());
+        room.reveal(0, 3, left(), [2; 16]);
+        assert_eq!(room.commitments, [None, Some(commitment)]);
+        let [first, second] = drain(&mut room);
+        assert!(matches!(
+            first[..],
+            [
+                ServerMessage::InvalidMove(Some(2), InvalidMove::Uncommitted),
+                ServerMessage::InvalidMove(Some(3), InvalidMove::Uncommitted)
+            ]
+        ));
+        assert!(second.is_empty());
+    }
+
+    #[test]
+    fn commitments_are_refused_once_submitted_or_when_off() {
+        let mut committed = room(true);
+        let commitment = Commitment::new(0, left(), [1; 16]);
+        committed.commit(0, 1, commitment);
+        drain(&mut committed);
+        committed.commit(0, 2, Commitment::new(0, left(), [2; 16]));
+        assert_eq!(committed.commitments[0], Some(commitment));
+        let mut plain = room(false);
+        plain.commit(0, 1, commitment);
+        assert_eq!(plain.commitments, [None; 2]);
+        let refusals = [drain(&mut committed), drain(&mut plain)].map(|[first, _]| first);
+        assert!(matches!(
+            refusals[0][..],
+            [ServerMessage::InvalidMove(
+                Some(2),
+                InvalidMove::AlreadySubmitted
+            )]
+        ));
+        assert!(matches!(
+            refusals[1][..],
+            [ServerMessage::InvalidMove(
+                Some(1),
+                InvalidMove::CommitRevealOff
+            )]
+        ));
+    }
+
+    #[test]
+    fn timeout_fills_in_moves_for_players_without_one() {
+        let mut room = timed(TimeoutAction::Wait);
+        room.set_input(0, left()).unwrap();
+        room.timeout();
+        assert_eq!(room.inputs[0], Some(left()));
+        // Waiting in place means attacking forward in time
+        let fallback = room.inputs[1].expect("A fallback is filled in");
+        assert_eq!(fallback.action_type, ActionType::Attack);
+        assert_eq!(fallback.direction.temporal, TemporalDirection::Forward);
+        assert!(room.needs_step);
+        assert_eq!(room.result, None);
+    }
+
+    #[test]
+    fn timeout_can_forfeit_players_without_a_move() {
+        let mut room = timed(TimeoutAction::Forfeit);
+        room.set_input(0, left()).unwrap();
+        room.timeout();
+        assert_eq!(room.result, Some(GameResult::Win(0)));
+        assert_eq!(room.turn_started, None);
+    }
+
+    fn clocked(time_bank: u32) -> Room {
+        playing(RoomConfig {
+            time_bank: Some(time_bank),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn clocks_only_run_for_players_still_to_move() {
+        let mut room = clocked(60);
+        room.set_input(0, left()).unwrap();
+        room.start_turn();
+        let clocks = room.clocks.as_ref().unwrap();
+        assert!(!clocks[0].status().running);
+        assert!(clocks[1].status().running);
+    }
+
+    #[test]
+    fn clocks_run_until_committed_actions_are_revealed() {
+        let mut room = playing(RoomConfig {
+            commit_reveal: true,
+            time_bank: Some(60),
+            ..Default::default()
+        });
+        room.start_turn();
+        let nonce = [1; 16];
+        let running = |room: &Room| {
+            let clocks = room.clocks.as_ref().unwrap();
+            clocks.each_ref().map(|clock| clock.status().running)
+        };
+        room.commit(0, 1, Commitment::new(0, left(), nonce));
+        assert_eq!(running(&room), [false, true]);
+        room.commit(1, 1, Commitment::new(1, left(), nonce));
+        assert_eq!(running(&room), [true, true]);
+        room.reveal(0, 2, left(), nonce);
+        assert_eq!(running(&room), [false, true]);
+    }
+
+    #[test]
+    fn tick_clocks_ends_the_game_when_a_clock_runs_out() {
+        let mut room = clocked(0);
+        room.clocks.as_mut().unwrap()[0].add(Duration::from_secs(60));
+        room.start_turn();
+        room.tick_clocks();
+        assert_eq!(room.result, Some(GameResult::Timeout(1)));
+        // Clocks stop with the game
+        let clocks = room.clocks.as_ref().unwrap();
+        assert!(clocks.iter().all(|clock| !clock.status().running));
+    }
+
+    #[test]
+    fn rooms_start_once_both_players_are_ready() {
+        let mut room = Room::new(0, RoomConfig::default(), false);
+        assert_eq!(room.join(0, 0, profile(0)), Some(0));
+        assert_eq!(room.phase, RoomPhase::Waiting);
+        assert_eq!(room.join(1, 0, profile(1)), Some(1));
+        assert_eq!(room.join(2, 0, profile(2)), None);
+        assert_eq!(room.phase, RoomPhase::ReadyCheck);
+        room.set_ready(0, true);
+        assert_eq!(room.phase, RoomPhase::ReadyCheck);
+        room.set_ready(1, true);
+        assert_eq!(room.phase, RoomPhase::Countdown);
+        room.countdown_started = Instant::now().checked_sub(START_COUNTDOWN);
+        room.tick_lobby();
+        assert_eq!(room.phase, RoomPhase::InProgress);
+    }
+
+    #[test]
+    fn leaving_during_the_countdown_cancels_it() {
+        let mut room = seated(RoomConfig::default());
+        room.set_ready(0, true);
+        room.set_ready(1, true);
+        room.leave(1, false);
+        assert_eq!(room.phase, RoomPhase::Waiting);
+        assert_eq!(room.lobby_ready, [false; 2]);
+        assert_eq!(room.countdown_started, None);
+    }
+
+    #[test]
+    fn moves_are_refused_before_the_game_starts() {
+        let mut room = seated(RoomConfig::default());
+        drain(&mut room);
+        room.submit(0, 1, left());
+        assert_eq!(room.inputs, [None; 2]);
+        let [first, _] = drain(&mut room);
+        assert!(matches!(
+            first[..],
+            [ServerMessage::InvalidMove(
+                Some(1),
+                InvalidMove::NotInProgress
+            )]
+        ));
+    }
+
+    #[test]
+    fn rematches_need_an_offer_and_an_answer() {
+        let mut room = room(false);
+        room.finish(GameResult::Win(0));
+        assert_eq!(room.phase, RoomPhase::Finished);
+        // There's nothing to accept yet
+        room.offer_rematch(1, true);
+        assert_eq!(room.rematch, [false; 2]);
+        room.offer_rematch(0, false);
+        assert_eq!(room.phase, RoomPhase::Finished);
+        room.offer_rematch(1, true);
+        assert_eq!(room.phase, RoomPhase::Countdown);
+        assert_eq!(room.result, None);
+        assert_eq!(room.score.wins, [1, 0]);
+    }
+
+    #[test]
+    fn rematches_can_swap_spawns() {
+        let mut room = playing(RoomConfig {
+            swap_spawns: true,
+            ..Default::default()
+        });
+        for expected in [true, false] {
+            room.finish(GameResult::Draw);
+            room.offer_rematch(0, false);
+            room.offer_rematch(1, true);
+            assert_eq!(room.replay.spawns_swapped, expected);
+        }
+    }
+
+    #[test]
+    fn finished_series_start_over() {
+        let mut room = playing(RoomConfig {
+            best_of: Some(3),
+            ..Default::default()
+        });
+        for _ in 0..2 {
+            room.finish(GameResult::Win(1));
+            room.offer_rematch(0, false);
+            room.offer_rematch(1, true);
+        }
+        assert_eq!(room.score.wins, [0, 0]);
+    }
+
+    #[test]
+    fn resigning_only_ends_games_in_progress() {
+        let mut waiting = Room::new(0, RoomConfig::default(), false);
+        waiting.resign(0);
+        assert_eq!(waiting.result, None);
+        let mut room = room(false);
+        room.resign(1);
+        assert_eq!(room.result, Some(GameResult::Resignation(1)));
+    }
+
+    #[test]
+    fn draws_need_an_offer_and_an_answer() {
+        let mut room = room(false);
+        room.offer_draw(0, true);
+        assert_eq!(room.draw_offers, [false; 2]);
+        room.offer_draw(0, false);
+        room.offer_draw(1, true);
+        assert_eq!(room.result, Some(GameResult::AgreedDraw));
+        assert!(matches!(
+            room.replay.events[..],
+            [(0, ReplayEvent::DrawOffered(0))]
+        ));
+    }
+
+    #[test]
+    fn draw_offers_lapse_after_a_turn() {
+        let mut room = room(false);
+        room.offer_draw(0, false);
+        room.submit(0, 1, left());
+        room.submit(1, 1, left());
+        room.step();
+        assert_eq!(room.draw_offers, [false; 2]);
+    }
+
+    #[test]
+    fn pausing_needs_both_players_and_stops_the_clocks() {
+        let mut room = clocked(60);
+        room.start_turn();
+        room.request_pause(0, false);
+        assert_eq!(room.phase, RoomPhase::InProgress);
+        room.request_pause(1, false);
+        assert_eq!(room.phase, RoomPhase::Paused);
+        let clocks = room.clocks.as_ref().unwrap();
+        assert!(clocks.iter().all(|clock| !clock.status().running));
+        room.request_pause(0, true);
+        room.request_pause(1, true);
+        assert_eq!(room.phase, RoomPhase::InProgress);
+        let clocks = room.clocks.as_ref().unwrap();
+        assert!(clocks.iter().all(|clock| clock.status().running));
+    }
+
+    #[test]
+    fn players_see_their_opponent_come_and_go() {
+        let mut room = seated(RoomConfig::default());
+        let [first, _] = drain(&mut room);
+        assert!(first
+            .iter()
+            .any(|message| matches!(message, ServerMessage::Connected([true, true]))));
+        room.leave(1, false);
+        let [first, _] = drain(&mut room);
+        assert!(first
+            .iter()
+            .any(|blob| matches!(blob, ServerMessage::Connected([true, false]))));
+    }
+
+    #[test]
+    fn answers_carry_the_request_id() {
+        let mut room = room(false);
+        room.submit(0, 7, left());
+        let back = action(SpatialDirection::Left, TemporalDirection::Backward);
+        room.submit(1, 8, back);
+        let [first, second] = drain(&mut room);
+        assert!(first
+            .iter()
+            .any(|message| matches!(message, ServerMessage::ActionAccepted(7))));
+        assert!(matches!(
+            second.last(),
+            Some(ServerMessage::InvalidMove(
+                Some(8),
+                InvalidMove::OutOfBounds
+            ))
+        ));
+    }
+
+    #[test]
+    fn force_reset_discards_the_game_without_a_result() {
+        let mut room = room(false);
+        room.submit(0, 1, left());
+        room.force_reset();
+        assert_eq!(room.phase, RoomPhase::ReadyCheck);
+        assert_eq!(room.result, None);
+        assert_eq!(room.inputs, [None; 2]);
+        assert_eq!(room.lobby_ready, [false; 2]);
+    }
+
+    #[test]
+    fn force_reset_releases_held_seats() {
+        let mut room = room(false);
+        room.leave(0, true);
+        room.force_reset();
+        assert_eq!(room.dropped, [None; 2]);
+        assert_eq!(room.phase, RoomPhase::Waiting);
+        // So the room closes once the other player leaves too
+        room.leave(1, false);
+        assert!(room.is_abandoned());
+    }
+
+    #[test]
+    fn finished_games_are_kept_for_the_server_to_record() {
+        let mut room = room(false);
+        room.resign(0);
+        assert_eq!(room.results, [GameResult::Resignation(0)]);
+        // Profiles stay after leaving, so the result still counts
+        room.leave(0, false);
+        assert!(room.profiles.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn bots_ready_up_and_move_once_the_game_starts() {
+        let mut room = Room::new(0, RoomConfig::default(), false);
+        room.join(0, 0, profile(0));
+        assert_eq!(room.add_bot(Difficulty::Easy), Some(1));
+        assert_eq!(room.add_bot(Difficulty::Easy), None);
+        assert_eq!(room.phase, RoomPhase::ReadyCheck);
+        room.tick();
+        assert_eq!(room.lobby_ready, [false, true]);
+        room.set_ready(0, true);
+        room.countdown_started = Instant::now().checked_sub(START_COUNTDOWN);
+        room.tick();
+        assert_eq!(room.phase, RoomPhase::InProgress);
+        assert!(room.inputs[1].is_some());
+    }
+
+    #[test]
+    fn committed_players_are_asked_to_reveal_on_timeout() {
+        let mut room = playing(RoomConfig {
+            commit_reveal: true,
+            turn_time: Some(10),
+            ..Default::default()
+        });
+        let nonce = [1; 16];
+        room.commit(0, 1, Commitment::new(0, left(), nonce));
+        drain(&mut room);
+        room.timeout();
+        assert_eq!(room.inputs[0], None);
+        assert!(room.inputs[1].is_some());
+        let [first, _] = drain(&mut room);
+        assert!(first
+            .iter()
+            .any(|message| matches!(message, ServerMessage::RevealRequested)));
+        room.reveal(0, 2, left(), nonce);
+        assert_eq!(room.inputs[0], Some(left()));
+        room.step();
+        assert_eq!(room.replay.turns[0].timed_out, [false, true]);
+        assert!(room.replay.verify());
+    }
+
+    #[test]
+    fn stalled_reveals_are_overruled_on_the_next_timeout() {
+        let mut room = playing(RoomConfig {
+            commit_reveal: true,
+            turn_time: Some(10),
+            ..Default::default()
+        });
+        room.commit(0, 1, Commitment::new(0, left(), [1; 16]));
+        room.timeout();
+        room.timeout();
+        assert!(room.inputs.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn moves_while_paused_keep_what_was_submitted() {
+        let mut room = room(false);
+        room.submit(0, 1, left());
+        room.request_pause(0, false);
+        room.request_pause(1, false);
+        drain(&mut room);
+        room.submit(0, 2, left());
+        assert_eq!(room.inputs[0], Some(left()));
+        let [first, _] = drain(&mut room);
+        assert!(matches!(
+            first[..],
+            [ServerMessage::InvalidMove(
+                Some(2),
+                InvalidMove::NotInProgress
+            )]
+        ));
+    }
+
+    #[test]
+    fn dropped_players_get_their_seat_back() {
+        let mut room = room(false);
+        room.submit(1, 1, left());
+        room.leave(0, true);
+        assert!(!room.is_abandoned());
+        // The held seat isn't free for anyone else
+        assert_eq!(room.join(2, 0, profile(2)), None);
+        let token = room.tokens[0];
+        assert_eq!(room.rejoin(3, 0, token.wrapping_add(1)), None);
+        drain(&mut room);
+        assert_eq!(room.rejoin(3, 0, token), Some((0, None)));
+        assert_eq!(room.seats[0], Some(3));
+        let [first, _] = drain(&mut room);
+        assert!(first
+            .iter()
+            .any(|message| matches!(message, ServerMessage::PlayerReady(1))));
+        // The token is only good once
+        assert_ne!(room.tokens[0], token);
+        assert_eq!(room.rejoin(4, 0, token), None);
+    }
+
+    #[test]
+    fn rejoining_takes_the_seat_from_a_stale_connection() {
+        let mut plain = seated(RoomConfig::default());
+        let token = plain.tokens[1];
+        let [_, second] = drain(&mut plain);
+        assert!(second
+            .iter()
+            .any(|message| matches!(message, ServerMessage::Assign(_, _, 1, t) if *t == token)));
+        assert_eq!(plain.rejoin(5, 0, token), Some((1, Some(1))));
+        assert_eq!(plain.seats[1], Some(5));
+        // A seat given up on purpose can't be reclaimed
+        let token = plain.tokens[1];
+        plain.leave(1, false);
+        assert_eq!(plain.rejoin(6, 0, token), None);
+    }
+
+    #[test]
+    fn dropped_players_forfeit_after_the_grace_period() {
+        let mut room = room(false);
+        room.leave(0, true);
+        room.dropped[0] = Instant::now().checked_sub(RECONNECT_GRACE);
+        room.tick();
+        assert_eq!(room.result, Some(GameResult::Resignation(0)));
+        assert_eq!(room.dropped, [None; 2]);
+    }
+
+    #[test]
+    fn seats_are_only_held_during_a_game() {
+        let mut room = seated(RoomConfig::default());
+        room.leave(0, true);
+        assert_eq!(room.dropped, [None; 2]);
+        room.leave(1, true);
+        assert!(room.is_abandoned());
+    }
+
+    #[test]
+    fn leaving_a_game_forfeits_it() {
+        let mut room = room(false);
+        room.leave(1, false);
+        assert_eq!(room.result, Some(GameResult::Resignation(1)));
+        assert_eq!(room.results, [GameResult::Resignation(1)]);
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use neurojam24_core::{GameResult, MatchScore, PlayerStatus, RoomId, RoomPhase};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{room::Room, Server};
+
+#[derive(Serialize)]
+struct RoomStatus {
+    id: RoomId,
+    rated: bool,
+    phase: RoomPhase,
+    players: [bool; 2], // Which seats are taken
+    names: [Option<String>; 2],
+    turn: usize,
+    player_stati: [PlayerStatus; 2],
+    result: Option<GameResult>,
+    score: MatchScore,
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Answers `GET /rooms` and `GET /leaderboard` with JSON and `GET /metrics` in Prometheus' format
+// until the server shuts down
+pub fn serve(listener: TcpListener, server: Arc<Mutex<Server>>) {
+    if let Err(error) = listener.set_nonblocking(true) {
+        warn!(%error, "Failed to configure the status port");
+        return;
+    }
+    while !server.lock().unwrap().shutting_down {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(_) => {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+        };
+        if let Err(error) = respond(stream, &server) {
+            warn!(%error, "Status request failed");
+        }
+    }
+}
+
+fn respond(mut stream: TcpStream, server: &Mutex<Server>) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/rooms") => {
+            let server = server.lock().unwrap();
+            let rooms: Vec<_> = server.rooms.values().map(room_status).collect();
+            let body = serde_json::to_string(&rooms).expect("Room statuses serialise");
+            ("200 OK", "application/json", body)
+        }
+        ("GET", "/leaderboard") => {
+            let leaderboard = server.lock().unwrap().profiles.leaderboard();
+            let body = serde_json::to_string(&leaderboard).expect("Profiles serialise");
+            ("200 OK", "application/json", body)
+        }
+        ("GET", "/metrics") => {
+            let server = server.lock().unwrap();
+            let body = server.metrics.render(
+                server.connections.len(),
+                server.rooms.len(),
+                server.queue.entries().len(),
+            );
+            ("200 OK", "text/plain; version=0.0.4", body)
+        }
+        ("GET", _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n".to_string(),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn room_status(room: &Room) -> RoomStatus {
+    RoomStatus {
+        id: room.id,
+        rated: room.rated,
+        phase: room.phase,
+        players: room.connected(),
+        names: room
+            .profiles
+            .each_ref()
+            .map(|profile| profile.as_ref().map(|profile| profile.name.clone())),
+        turn: room.game_status.turn(),
+        player_stati: room.game_status.player_stati,
+        result: room.result,
+        score: room.score,
+    }
+}